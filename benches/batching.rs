@@ -0,0 +1,116 @@
+use bevy::{
+    app::App,
+    asset::{AddAsset, AssetPlugin, Assets, Handle},
+    ecs::world::World,
+    math::{Vec2, Vec3},
+    render::mesh::Mesh,
+    sprite::{Mesh2dHandle, SpriteSheetBundle, TextureAtlas, TextureAtlasSprite},
+    transform::components::{GlobalTransform, Transform},
+    MinimalPlugins,
+};
+use bevy_2d_rpg::quads::{chunk_tiles, map_chunks, quads_mesh, Quad};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+const MAP_SIZE: usize = 200;
+const TILE_SIZE: f32 = 0.1;
+
+//Same layout as basictiles.png
+fn atlas() -> TextureAtlas {
+    TextureAtlas::from_grid(Handle::default(), Vec2::splat(16.0), 8, 15)
+}
+
+fn tile_index(x: i32, y: i32) -> usize {
+    ((x * 7 + y * 13) % 120) as usize
+}
+
+fn tile_center(x: i32, y: i32) -> Vec3 {
+    Vec3::new(x as f32 * TILE_SIZE, -(y as f32) * TILE_SIZE, 100.0)
+}
+
+//How the map used to be drawn, one sprite per tile
+fn spawn_sprites(world: &mut World) {
+    for y in 0..MAP_SIZE as i32 {
+        for x in 0..MAP_SIZE as i32 {
+            world.spawn().insert_bundle(SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    index: tile_index(x, y),
+                    custom_size: Some(Vec2::splat(TILE_SIZE)),
+                    ..Default::default()
+                },
+                transform: Transform::from_translation(tile_center(x, y)),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+//A world with Assets<Mesh>, like the game's
+fn mesh_world() -> World {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin)
+        .add_asset::<Mesh>();
+    app.world
+}
+
+//One mesh per chunk, added to Assets<Mesh> the way MeshBatcher does
+fn spawn_chunks(world: &mut World, atlas: &TextureAtlas) {
+    for chunk in map_chunks(MAP_SIZE, MAP_SIZE) {
+        let quads: Vec<Quad> = chunk_tiles(chunk, MAP_SIZE, MAP_SIZE)
+            .map(|(x, y)| Quad {
+                center: tile_center(x, y),
+                size: Vec2::splat(TILE_SIZE),
+                index: tile_index(x, y),
+            })
+            .collect();
+        let mesh = world
+            .resource_mut::<Assets<Mesh>>()
+            .add(quads_mesh(atlas, &quads));
+        world.spawn().insert_bundle((
+            Transform::default(),
+            GlobalTransform::default(),
+            Mesh2dHandle(mesh),
+        ));
+    }
+}
+
+fn batching_benchmark(c: &mut Criterion) {
+    let atlas = atlas();
+
+    let mut world = World::new();
+    spawn_sprites(&mut world);
+    let sprites = world.entities().len();
+    let mut world = mesh_world();
+    let before = world.entities().len();
+    spawn_chunks(&mut world, &atlas);
+    let chunks = world.entities().len() - before;
+    println!(
+        "{}x{} map: {} sprite entities, {} chunk entities",
+        MAP_SIZE, MAP_SIZE, sprites, chunks
+    );
+
+    let mut group = c.benchmark_group("map spawn 200x200");
+    group.sample_size(20);
+    group.bench_function("sprite per tile", |b| {
+        b.iter(|| {
+            let mut world = World::new();
+            spawn_sprites(&mut world);
+            black_box(world)
+        })
+    });
+    //Setting up the asset server isn't part of spawning a map, so it isn't timed
+    group.bench_function("mesh per chunk", |b| {
+        b.iter_batched(
+            mesh_world,
+            |mut world| {
+                spawn_chunks(&mut world, &atlas);
+                black_box(world)
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, batching_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+use bevy::{
+    math::{Vec2, Vec3},
+    sprite::collide_aabb::collide,
+};
+use bevy_2d_rpg::collision::{CollisionMap, TileFlags};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const MAP_SIZE: usize = 500;
+
+//Walled border with scattered pillars, roughly what a large overworld looks like
+fn build_map() -> (CollisionMap, Vec<Vec3>) {
+    let mut map = CollisionMap::new(MAP_SIZE, MAP_SIZE);
+    let mut walls = Vec::new();
+    for y in 0..MAP_SIZE {
+        for x in 0..MAP_SIZE {
+            let border = x == 0 || y == 0 || x == MAP_SIZE - 1 || y == MAP_SIZE - 1;
+            if border || (x * 7 + y * 13) % 11 == 0 {
+                map.set(
+                    x as i32,
+                    y as i32,
+                    TileFlags {
+                        solid: true,
+                        encounter_zone: None,
+                    },
+                );
+                walls.push(Vec3::new(x as f32, y as f32, 0.0));
+            }
+        }
+    }
+    (map, walls)
+}
+
+fn collision_benchmark(c: &mut Criterion) {
+    let (map, walls) = build_map();
+    let player = Vec3::new(250.3, 250.6, 0.0);
+
+    let mut group = c.benchmark_group("player collision 500x500");
+    group.bench_function("linear scan", |b| {
+        b.iter(|| {
+            walls.iter().any(|wall| {
+                collide(black_box(player), Vec2::splat(0.9), *wall, Vec2::splat(1.0)).is_some()
+            })
+        })
+    });
+    group.bench_function("collision map", |b| {
+        b.iter(|| map.overlaps_solid(black_box(player.truncate()), 0.45))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, collision_benchmark);
+criterion_main!(benches);
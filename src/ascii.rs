@@ -1,16 +1,25 @@
 use bevy::prelude::*;
+use bevy_2d_rpg::quads::Quad;
 
-use crate::TILE_SIZE;
+use crate::{batch::MeshBatcher, TILE_SIZE};
 
 pub struct AsciiPlugin;
 
 pub struct AsciiSheet(pub Handle<TextureAtlas>);
 
 #[derive(Component)]
-pub struct AsciiText;
+pub struct AsciiText {
+    pub text: String,
+    pub color: Color,
+}
 
 #[derive(Component)]
-pub struct NineSlice;
+pub struct NineSlice {
+    //In tiles
+    pub width: f32,
+    pub height: f32,
+    pub color: Color,
+}
 
 #[derive(Copy, Clone)]
 pub struct NineSliceIndices {
@@ -26,6 +35,9 @@ pub struct NineSliceIndices {
 impl Plugin for AsciiPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system_to_stage(StartupStage::PreStartup, load_ascii)
+            //Late so text spawned or changed this frame is drawn right away
+            .add_system_to_stage(CoreStage::PostUpdate, mesh_ascii_text)
+            .add_system_to_stage(CoreStage::PostUpdate, mesh_nine_slices)
             .insert_resource(NineSliceIndices {
                 center: 2 * 16,
                 upper_left: 13 * 16 + 10,
@@ -38,139 +50,99 @@ impl Plugin for AsciiPlugin {
     }
 }
 
-pub fn spawn_nine_slice(
-    commands: &mut Commands,
-    ascii: &AsciiSheet,
-    indices: &NineSliceIndices,
-    width: f32,
-    height: f32,
-) -> Entity {
+pub fn spawn_nine_slice(commands: &mut Commands, width: f32, height: f32) -> Entity {
     assert!(width >= 2.0);
     assert!(height >= 2.0);
 
-    let color = Color::rgb(0.3, 0.3, 0.9);
-    let mut sprites = Vec::new();
-
-    let left = (-width / 2.0 + 0.5) * TILE_SIZE;
-    let right = -left;
-    let down = (-height / 2.0 + 0.5) * TILE_SIZE;
-    let up = -down;
-
-    sprites.push(spawn_ascii_sprite(
-        commands,
-        ascii,
-        indices.center,
-        color,
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(width - 2.0, height - 2.0, 0.0),
-    ));
-    sprites.push(spawn_ascii_sprite(
-        commands,
-        ascii,
-        indices.upper_left,
-        color,
-        Vec3::new(left, up, 0.0),
-        Vec3::splat(1.0),
-    ));
-    sprites.push(spawn_ascii_sprite(
-        commands,
-        ascii,
-        indices.vertical,
-        color,
-        Vec3::new(left, 0.0, 0.0),
-        Vec3::new(1.0, height - 2.0, 1.0),
-    ));
-    sprites.push(spawn_ascii_sprite(
-        commands,
-        ascii,
-        indices.lower_left,
-        color,
-        Vec3::new(left, down, 0.0),
-        Vec3::splat(1.0),
-    ));
-    sprites.push(spawn_ascii_sprite(
-        commands,
-        ascii,
-        indices.horizontal,
-        color,
-        Vec3::new(0.0, down, 0.0),
-        Vec3::new(width - 2.0, 1.0, 1.0),
-    ));
-    sprites.push(spawn_ascii_sprite(
-        commands,
-        ascii,
-        indices.horizontal,
-        color,
-        Vec3::new(0.0, up, 0.0),
-        Vec3::new(width - 2.0, 1.0, 1.0),
-    ));
-    sprites.push(spawn_ascii_sprite(
-        commands,
-        ascii,
-        indices.upper_right,
-        color,
-        Vec3::new(right, up, 0.0),
-        Vec3::splat(1.0),
-    ));
-    sprites.push(spawn_ascii_sprite(
-        commands,
-        ascii,
-        indices.vertical,
-        color,
-        Vec3::new(right, 0.0, 0.0),
-        Vec3::new(1.0, height - 2.0, 1.0),
-    ));
-    sprites.push(spawn_ascii_sprite(
-        commands,
-        ascii,
-        indices.lower_right,
-        color,
-        Vec3::new(right, down, 0.0),
-        Vec3::splat(1.0),
-    ));
-
     commands
-        .spawn()
-        .insert(NineSlice)
+        .spawn_bundle(batch_bundle(Vec3::ZERO))
+        .insert(NineSlice {
+            width,
+            height,
+            color: Color::rgb(0.3, 0.3, 0.9),
+        })
         .insert(Name::new("NinceSliceBox"))
-        .insert(Transform::default())
-        .insert(GlobalTransform::default())
-        .push_children(&sprites)
         .id()
 }
 
-pub fn spawn_ascii_text(
-    commands: &mut Commands,
-    ascii: &AsciiSheet,
-    to_print: &str,
-    left_center: Vec3,
-) -> Entity {
-    let color = Color::rgb(0.8, 0.8, 0.8);
-    let mut character_sprites = Vec::new();
-    for (i, char) in to_print.chars().enumerate() {
-        assert!(char as usize <= 255);
-        character_sprites.push(spawn_ascii_sprite(
-            commands,
-            ascii,
-            char as usize,
-            color,
-            Vec3::new(i as f32 * TILE_SIZE, 0.0, 0.0),
-            Vec3::splat(1.0),
-        ));
-    }
+pub fn spawn_ascii_text(commands: &mut Commands, to_print: &str, left_center: Vec3) -> Entity {
+    assert!(to_print.chars().all(|char| char as usize <= 255));
     commands
-        .spawn()
+        .spawn_bundle(batch_bundle(left_center))
         .insert(Name::new(format!("Text - {}", to_print)))
-        .insert(AsciiText)
-        .insert(Transform {
-            translation: left_center,
-            ..Default::default()
+        .insert(AsciiText {
+            text: to_print.to_string(),
+            color: Color::rgb(0.8, 0.8, 0.8),
         })
-        .insert(GlobalTransform::default())
-        .push_children(&character_sprites)
         .id()
 }
 
+//What a text or nine slice entity needs to be drawn once its mesh is built
+fn batch_bundle(translation: Vec3) -> (Transform, GlobalTransform, Visibility, ComputedVisibility) {
+    (
+        Transform::from_translation(translation),
+        GlobalTransform::default(),
+        Visibility::default(),
+        ComputedVisibility::default(),
+    )
+}
+
+//Each text is drawn as a single mesh, rebuilt whenever the text or colour changes
+fn mesh_ascii_text(
+    mut batcher: MeshBatcher,
+    text_query: Query<(Entity, &AsciiText), Changed<AsciiText>>,
+    ascii: Res<AsciiSheet>,
+) {
+    for (entity, text) in text_query.iter() {
+        let quads: Vec<Quad> = text
+            .text
+            .chars()
+            .enumerate()
+            .map(|(i, char)| Quad {
+                center: Vec3::new(i as f32 * TILE_SIZE, 0.0, 0.0),
+                size: Vec2::splat(TILE_SIZE),
+                index: char as usize,
+            })
+            .collect();
+        batcher.insert_batch(entity, &ascii.0, text.color, &quads);
+    }
+}
+
+fn mesh_nine_slices(
+    mut batcher: MeshBatcher,
+    nine_slice_query: Query<(Entity, &NineSlice), Changed<NineSlice>>,
+    ascii: Res<AsciiSheet>,
+    indices: Res<NineSliceIndices>,
+) {
+    for (entity, nine_slice) in nine_slice_query.iter() {
+        let (width, height) = (nine_slice.width, nine_slice.height);
+        let left = (-width / 2.0 + 0.5) * TILE_SIZE;
+        let right = -left;
+        let down = (-height / 2.0 + 0.5) * TILE_SIZE;
+        let up = -down;
+        let inner = Vec2::new(width - 2.0, height - 2.0) * TILE_SIZE;
+        let corner = Vec2::splat(TILE_SIZE);
+
+        let quad = |x: f32, y: f32, size: Vec2, index: usize| Quad {
+            center: Vec3::new(x, y, 0.0),
+            size,
+            index,
+        };
+        let quads = [
+            quad(0.0, 0.0, inner, indices.center),
+            quad(left, up, corner, indices.upper_left),
+            quad(left, 0.0, Vec2::new(TILE_SIZE, inner.y), indices.vertical),
+            quad(left, down, corner, indices.lower_left),
+            quad(0.0, down, Vec2::new(inner.x, TILE_SIZE), indices.horizontal),
+            quad(0.0, up, Vec2::new(inner.x, TILE_SIZE), indices.horizontal),
+            quad(right, up, corner, indices.upper_right),
+            quad(right, 0.0, Vec2::new(TILE_SIZE, inner.y), indices.vertical),
+            quad(right, down, corner, indices.lower_right),
+        ];
+        batcher.insert_batch(entity, &ascii.0, nine_slice.color, &quads);
+    }
+}
+
 pub fn spawn_ascii_sprite(
     commands: &mut Commands,
     ascii: &AsciiSheet,
@@ -3,6 +3,8 @@ use bevy_kira_audio::{Audio, AudioChannel, AudioPlugin, AudioSource};
 
 use crate::combat::CombatState;
 use crate::combat::FightEvent;
+use crate::encounter::{ActiveEncounter, EncounterZones};
+use crate::input::{InputAction, InputActions};
 use crate::GameState;
 
 pub struct GameAudioPlugin;
@@ -15,6 +17,8 @@ pub struct AudioState {
     reward_handle: Handle<AudioSource>,
     mainmenu_handle: Handle<AudioSource>,
     buttonclic_handle: Handle<AudioSource>,
+    //Indexed by encounter zone
+    zone_music_handles: Vec<Handle<AudioSource>>,
 
     bgm_channel: AudioChannel,
     combat_channel: AudioChannel,
@@ -67,12 +71,17 @@ fn resume_bgm_music(audio: Res<Audio>, audio_state: Res<AudioState>) {
     audio.resume_channel(&audio_state.bgm_channel);
 }
 
-fn start_combat_music(audio: Res<Audio>, audio_state: Res<AudioState>) {
+fn start_combat_music(
+    audio: Res<Audio>,
+    audio_state: Res<AudioState>,
+    active_encounter: Option<Res<ActiveEncounter>>,
+) {
+    let music = active_encounter
+        .and_then(|encounter| encounter.zone)
+        .and_then(|zone| audio_state.zone_music_handles.get(zone))
+        .unwrap_or(&audio_state.combat_handle);
     audio.pause_channel(&audio_state.bgm_channel);
-    audio.play_looped_in_channel(
-        audio_state.combat_handle.clone(),
-        &audio_state.combat_channel,
-    );
+    audio.play_looped_in_channel(music.clone(), &audio_state.combat_channel);
 }
 
 fn start_bgm_music(audio: Res<Audio>, audio_state: Res<AudioState>) {
@@ -88,21 +97,26 @@ fn start_menu_music(audio: Res<Audio>, audio_state: Res<AudioState>) {
 }
 
 fn volume_control(
-    keyboard: Res<Input<KeyCode>>,
+    actions: Res<InputActions>,
     audio: Res<Audio>,
     mut audio_state: ResMut<AudioState>,
 ) {
-    if keyboard.just_pressed(KeyCode::Up) {
+    if actions.just_pressed(InputAction::VolumeUp) {
         audio_state.volume += 0.10;
     }
-    if keyboard.just_pressed(KeyCode::Down) {
+    if actions.just_pressed(InputAction::VolumeDown) {
         audio_state.volume -= 0.10;
     }
     audio_state.volume = audio_state.volume.clamp(0.0, 1.0);
     audio.set_volume_in_channel(audio_state.volume, &audio_state.bgm_channel);
 }
 
-fn load_audio(mut commands: Commands, audio: Res<Audio>, assets: Res<AssetServer>) {
+fn load_audio(
+    mut commands: Commands,
+    audio: Res<Audio>,
+    assets: Res<AssetServer>,
+    zones: Res<EncounterZones>,
+) {
     let bgm_handle = assets.load("audio/music/deepwater-ruins.ogg");
     let combat_handle = assets.load("audio/music/Of Far Different Nature - 0 to 100 (CC-BY).ogg");
     let mainmenu_handle = assets.load("audio/music/bip-bop.ogg");
@@ -110,6 +124,11 @@ fn load_audio(mut commands: Commands, audio: Res<Audio>, assets: Res<AssetServer
     let hit2_handle = assets.load("audio/sfx/Hit_hurt 2.wav");
     let reward_handle = assets.load("audio/sfx/Powerup.wav");
     let buttonclic_handle = assets.load("audio/sfx/Button_clic.wav");
+    let zone_music_handles = zones
+        .0
+        .iter()
+        .map(|zone| assets.load(zone.music.as_str()))
+        .collect();
 
     let bgm_channel = AudioChannel::new("bgm".to_string());
     let combat_channel = AudioChannel::new("combat".to_string());
@@ -128,6 +147,7 @@ fn load_audio(mut commands: Commands, audio: Res<Audio>, assets: Res<AssetServer
         reward_handle: reward_handle,
         mainmenu_handle: mainmenu_handle,
         buttonclic_handle: buttonclic_handle,
+        zone_music_handles,
 
         bgm_channel,
         combat_channel,
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use bevy::{ecs::system::SystemParam, prelude::*, sprite::Mesh2dHandle};
+use bevy_2d_rpg::quads::{quads_mesh, Quad};
+
+pub struct BatchPlugin;
+
+//Materials shared by every batch with the same texture and tint
+#[derive(Default)]
+pub struct BatchMaterials(HashMap<(Handle<Image>, u32), Handle<ColorMaterial>>);
+
+impl Plugin for BatchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BatchMaterials>();
+    }
+}
+
+//Turns lists of atlas quads into single meshes, so a map chunk or a line of text is one entity
+#[derive(SystemParam)]
+pub struct MeshBatcher<'w, 's> {
+    pub commands: Commands<'w, 's>,
+    atlases: Res<'w, Assets<TextureAtlas>>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    materials: ResMut<'w, Assets<ColorMaterial>>,
+    cache: ResMut<'w, BatchMaterials>,
+}
+
+impl<'w, 's> MeshBatcher<'w, 's> {
+    //Gives an entity one mesh for all the quads, replacing the one it had
+    pub fn insert_batch(
+        &mut self,
+        entity: Entity,
+        atlas: &Handle<TextureAtlas>,
+        color: Color,
+        quads: &[Quad],
+    ) {
+        let atlas = match self.atlases.get(atlas) {
+            Some(atlas) => atlas,
+            None => return,
+        };
+        let materials = &mut self.materials;
+        let material = self
+            .cache
+            .0
+            .entry((atlas.texture.clone(), color.as_rgba_u32()))
+            .or_insert_with(|| {
+                materials.add(ColorMaterial {
+                    color,
+                    texture: Some(atlas.texture.clone()),
+                })
+            })
+            .clone();
+        let mesh = self.meshes.add(quads_mesh(atlas, quads));
+        self.commands
+            .entity(entity)
+            .insert(Mesh2dHandle(mesh))
+            .insert(material);
+    }
+
+    pub fn spawn_batch(
+        &mut self,
+        atlas: &Handle<TextureAtlas>,
+        color: Color,
+        quads: &[Quad],
+        transform: Transform,
+    ) -> Entity {
+        let entity = self
+            .commands
+            .spawn_bundle((
+                transform,
+                GlobalTransform::from(transform),
+                Visibility::default(),
+                ComputedVisibility::default(),
+            ))
+            .id();
+        self.insert_batch(entity, atlas, color, quads);
+        entity
+    }
+}
@@ -0,0 +1,64 @@
+//Dumps generated dungeon floors as text maps for inspection:
+//  cargo run --bin dungeon-gen [--floor N] [--out DIR] SEED ...
+//Prints each floor when no output directory is given, otherwise writes DIR/dungeon_<seed>_<floor>.map
+use std::{env, fs, path::PathBuf, process};
+
+use bevy_2d_rpg::dungeon::{generate, map_name, DungeonSettings};
+
+fn usage() -> ! {
+    eprintln!("usage: dungeon-gen [--floor N] [--out DIR] SEED ...");
+    process::exit(1);
+}
+
+fn main() {
+    let mut floor = 1;
+    let mut out = None;
+    let mut seeds = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--floor" => {
+                floor = match args.next().and_then(|floor| floor.parse().ok()) {
+                    Some(floor) if floor > 0 => floor,
+                    _ => usage(),
+                }
+            }
+            "--out" => out = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            seed => match seed.parse::<u64>() {
+                Ok(seed) => seeds.push(seed),
+                Err(_) => usage(),
+            },
+        }
+    }
+    if seeds.is_empty() {
+        usage();
+    }
+
+    let settings = DungeonSettings::default();
+    for seed in seeds {
+        let dungeon = generate(seed, floor, &settings);
+        let mut summary = format!(
+            "{}: {} rooms, boss at {:?}\n",
+            map_name(seed, floor),
+            dungeon.rooms.len(),
+            dungeon.boss
+        );
+        for (tile, item) in dungeon.chests.iter() {
+            summary.push_str(&format!("  chest at {:?}: {}\n", tile, item));
+        }
+        print!("{}", summary);
+
+        let text = dungeon.data.to_text();
+        match &out {
+            Some(dir) => {
+                let path = dir.join(format!("dungeon_{}_{}.map", seed, floor));
+                if let Err(err) = fs::write(&path, text) {
+                    eprintln!("{}: {}", path.display(), err);
+                    process::exit(1);
+                }
+                println!("  wrote {}", path.display());
+            }
+            None => println!("{}", text),
+        }
+    }
+}
@@ -0,0 +1,616 @@
+//Checks map files with the parser the game uses, for the content pipeline:
+//  cargo run --bin map-check [assets/maps/town.map assets/maps/meadow.tmj ...]
+//Every .map, .tmj and .tmx in assets/maps is checked when no files are given, .ron objects
+//files are skipped as they are checked with their map. Exits with 1 on any error
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    env, fmt, fs,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    process,
+};
+
+use bevy_2d_rpg::{
+    dungeon::{self, DungeonSettings},
+    map::{layer_header, Legend, MapData, MapLayer, ObjectType, DEFAULT_SPAWN},
+    tiled::{parse_tiles, TiledMap},
+};
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+const MAPS_DIR: &str = "assets/maps";
+//In the order the game looks for them
+const MAP_EXTENSIONS: [&str; 3] = ["map", "tmj", "tmx"];
+const LEGEND_PATH: &str = "assets/maps/legend.ron";
+//Unreachable tiles listed before the rest are only counted
+const MAX_LISTED: usize = 5;
+
+//A map in the order it was written, so repeated keys aren't silently merged
+struct Entries<K, V>(Vec<(K, V)>);
+
+impl<K, V> Default for Entries<K, V> {
+    fn default() -> Self {
+        Entries(Vec::new())
+    }
+}
+
+impl<'de, K: Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de> for Entries<K, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor<K, V>(PhantomData<(K, V)>);
+
+        impl<'de, K: Deserialize<'de>, V: Deserialize<'de>> Visitor<'de> for EntriesVisitor<K, V> {
+            type Value = Entries<K, V>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a map")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = access.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(Entries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor(PhantomData))
+    }
+}
+
+#[derive(Deserialize)]
+struct WarpTarget {
+    map: String,
+    spawn: String,
+}
+
+//The parts of <name>.ron the checks need, everything else is skipped
+#[derive(Default, Deserialize)]
+struct ObjectsFile {
+    #[serde(default)]
+    warps: Entries<(i32, i32), WarpTarget>,
+    #[serde(default)]
+    spawns: Entries<String, (i32, i32)>,
+    #[serde(default)]
+    switches: HashMap<(i32, i32), Vec<(i32, i32)>>,
+}
+
+struct Report {
+    file: PathBuf,
+    errors: Vec<String>,
+}
+
+impl Report {
+    fn error(&mut self, message: impl Into<String>) {
+        self.errors.push(message.into());
+    }
+}
+
+fn main() {
+    let legend = fs::read_to_string(LEGEND_PATH)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| Legend::from_ron(&contents).map_err(|err| err.to_string()))
+        .unwrap_or_else(|err| {
+            eprintln!("{}: {}", LEGEND_PATH, err);
+            process::exit(1);
+        });
+
+    let mut files: Vec<PathBuf> = env::args().skip(1).map(PathBuf::from).collect();
+    if files.is_empty() {
+        files = fs::read_dir(MAPS_DIR)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| is_map_file(path))
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+    }
+
+    let mut failed = false;
+    for file in files {
+        if extension(&file) == Some("ron") {
+            continue;
+        }
+        let report = check_map(&file, &legend);
+        if report.errors.is_empty() {
+            println!("{}: ok", report.file.display());
+        } else {
+            failed = true;
+            for error in report.errors.iter() {
+                println!("{}: {}", report.file.display(), error);
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|ext| ext.to_str())
+}
+
+fn is_map_file(path: &Path) -> bool {
+    extension(path).is_some_and(|ext| MAP_EXTENSIONS.contains(&ext))
+}
+
+fn check_map(file: &Path, legend: &Legend) -> Report {
+    let mut report = Report {
+        file: file.to_path_buf(),
+        errors: Vec::new(),
+    };
+    match extension(file) {
+        Some("map") => check_text_map(file, legend, &mut report),
+        Some("tmj") | Some("tmx") => check_tiled_map(file, &mut report),
+        _ => report.error("not a map file, expected .map, .tmj or .tmx"),
+    }
+    report
+}
+
+fn check_text_map(file: &Path, legend: &Legend, report: &mut Report) {
+    let contents = match fs::read_to_string(file) {
+        Ok(contents) => contents,
+        Err(err) => return report.error(err.to_string()),
+    };
+    let data = match MapData::parse(&contents) {
+        Ok(data) => data,
+        Err(err) => return report.error(err.to_string()),
+    };
+    let objects = load_objects(file, report);
+
+    check_ragged_rows(&contents, report);
+    check_glyphs(&data, legend, report);
+    check_layout(file, &data, legend, &objects, report);
+}
+
+//Tiled maps bring their own legend, built from the tile properties like the game does
+fn check_tiled_map(file: &Path, report: &mut Report) {
+    let tiled = match TiledMap::load(file) {
+        Ok(tiled) => tiled,
+        Err(err) => return report.error(err.to_string()),
+    };
+    let tiles = tiled.import_tiles();
+    for gid in tiles.skipped.iter() {
+        report.error(format!("gid {} is too large to be imported", gid));
+    }
+    let objects = tiled_objects(&tiled);
+    check_layout(file, &tiles.data, &tiles.legend, &objects, report);
+}
+
+//Checks shared by every kind of map
+fn check_layout(
+    file: &Path,
+    data: &MapData,
+    legend: &Legend,
+    objects: &ObjectsFile,
+    report: &mut Report,
+) {
+    let spawns = spawn_points(data, legend, objects);
+    check_enclosed(data, legend, objects, report);
+    check_spawns(data, legend, &spawns, report);
+    check_reachable(data, legend, objects, &spawns, report);
+    check_warps(file, legend, objects, report);
+}
+
+//Named spawns from <name>.ron followed by the spawn glyphs, which all count as the default spawn
+fn spawn_points(
+    data: &MapData,
+    legend: &Legend,
+    objects: &ObjectsFile,
+) -> Vec<(String, (i32, i32))> {
+    let markers = data
+        .find_objects(legend, ObjectType::Spawn)
+        .map(|tile| (DEFAULT_SPAWN.to_string(), tile));
+    objects.spawns.0.iter().cloned().chain(markers).collect()
+}
+
+//The objects of a Tiled map read the way the game imports them
+fn tiled_objects(tiled: &TiledMap) -> ObjectsFile {
+    let mut objects = ObjectsFile::default();
+    for object in tiled.objects() {
+        let position = tiled.object_tile(object);
+        let property = |name: &str| object.properties.get(name).cloned();
+        match object.kind.to_lowercase().as_str() {
+            "spawn" => {
+                let name = match object.name.as_str() {
+                    "" => DEFAULT_SPAWN.to_string(),
+                    name => name.to_string(),
+                };
+                objects.spawns.0.push((name, position));
+            }
+            "warp" => {
+                if let (Some(map), Some(spawn)) = (property("map"), property("spawn")) {
+                    for tile in tiled.object_tiles(object) {
+                        let warp = WarpTarget {
+                            map: map.clone(),
+                            spawn: spawn.clone(),
+                        };
+                        objects.warps.0.push((tile, warp));
+                    }
+                }
+            }
+            "switch" => {
+                let targets = parse_tiles(&property("targets").unwrap_or_default());
+                objects.switches.insert(position, targets);
+            }
+            _ => {}
+        }
+    }
+    objects
+}
+
+fn load_objects(file: &Path, report: &mut Report) -> ObjectsFile {
+    let path = file.with_extension("ron");
+    match fs::read_to_string(&path) {
+        Ok(contents) => ron::from_str(&contents).unwrap_or_else(|err| {
+            report.error(format!("{}: {}", path.display(), err));
+            ObjectsFile::default()
+        }),
+        Err(_) => ObjectsFile::default(),
+    }
+}
+
+//The parser pads short rows with void, which is almost never what was meant for the ground
+fn check_ragged_rows(contents: &str, report: &mut Report) {
+    let mut in_ground = false;
+    let mut rows = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        if let Some(name) = layer_header(line) {
+            in_ground = name == "ground";
+            continue;
+        }
+        if in_ground {
+            rows.push((index + 1, line.trim_end().chars().count()));
+        }
+    }
+    while rows.last().is_some_and(|(_, width)| *width == 0) {
+        rows.pop();
+    }
+
+    let width = rows.iter().map(|(_, width)| *width).max().unwrap_or(0);
+    for (line, row_width) in rows {
+        if row_width != width {
+            report.error(format!(
+                "line {}: ground row is {} tiles wide, the map is {}",
+                line, row_width, width
+            ));
+        }
+    }
+}
+
+fn check_glyphs(data: &MapData, legend: &Legend, report: &mut Report) {
+    let mut seen = HashSet::new();
+    for layer in MapLayer::VISIBLE {
+        for (position, glyph) in data.glyphs(layer) {
+            if legend.get(glyph).is_none() && seen.insert(glyph) {
+                report.error(format!(
+                    "unknown glyph {:?} in the {} layer at {:?}",
+                    glyph,
+                    layer.name(),
+                    position
+                ));
+            }
+        }
+    }
+    for (position, glyph) in data.glyphs(MapLayer::Collision) {
+        if glyph != '#' && glyph != '.' && seen.insert(glyph) {
+            report.error(format!(
+                "unknown glyph {:?} in the collision layer at {:?}, only '#' and '.' are allowed",
+                glyph, position
+            ));
+        }
+    }
+}
+
+//Walkable tiles on the edge let the player walk off the map, unless they warp somewhere
+fn check_enclosed(data: &MapData, legend: &Legend, objects: &ObjectsFile, report: &mut Report) {
+    let warps: HashSet<(i32, i32)> = objects.warps.0.iter().map(|(tile, _)| *tile).collect();
+    let (width, height) = (data.width() as i32, data.height() as i32);
+    let border = (0..width)
+        .flat_map(|x| [(x, 0), (x, height - 1)])
+        .chain((0..height).flat_map(|y| [(0, y), (width - 1, y)]));
+
+    let mut open = HashSet::new();
+    for (x, y) in border {
+        if !data.is_solid(legend, x, y) && !warps.contains(&(x, y)) {
+            open.insert((x, y));
+        }
+    }
+    let mut open: Vec<_> = open.into_iter().collect();
+    open.sort();
+    for tile in open {
+        report.error(format!(
+            "map is not enclosed, {:?} is walkable on the edge",
+            tile
+        ));
+    }
+}
+
+fn check_spawns(
+    data: &MapData,
+    legend: &Legend,
+    spawns: &[(String, (i32, i32))],
+    report: &mut Report,
+) {
+    if spawns.is_empty() {
+        report.error("map has no spawn points, add a spawn glyph or a named spawn");
+    }
+
+    let mut names = HashSet::new();
+    let mut tiles = HashMap::new();
+    for (name, tile) in spawns.iter() {
+        if !names.insert(name) {
+            report.error(format!("spawn {} is defined twice", name));
+        }
+        if let Some(other) = tiles.insert(*tile, name) {
+            report.error(format!(
+                "spawns {} and {} share the tile {:?}",
+                other, name, tile
+            ));
+        }
+        if data.is_solid(legend, tile.0, tile.1) {
+            report.error(format!("spawn {} at {:?} is not walkable", name, tile));
+        }
+    }
+}
+
+//Flood fills from every spawn, counting doors and switched walls as open
+fn check_reachable(
+    data: &MapData,
+    legend: &Legend,
+    objects: &ObjectsFile,
+    spawns: &[(String, (i32, i32))],
+    report: &mut Report,
+) {
+    let switched: HashSet<(i32, i32)> = objects.switches.values().flatten().copied().collect();
+    let passable = |x: i32, y: i32| {
+        if !data.is_solid(legend, x, y) || switched.contains(&(x, y)) {
+            return true;
+        }
+        //A closed door is just an open door waiting to happen
+        MapLayer::VISIBLE.iter().any(|layer| {
+            legend
+                .get(data.get(*layer, x, y))
+                .is_some_and(|entry| entry.object == Some(ObjectType::Door))
+        })
+    };
+
+    let mut reached = HashSet::new();
+    let mut queue: VecDeque<(i32, i32)> = spawns
+        .iter()
+        .map(|(_, tile)| *tile)
+        .filter(|(x, y)| passable(*x, *y))
+        .collect();
+    if queue.is_empty() {
+        return;
+    }
+    reached.extend(queue.iter().copied());
+    while let Some((x, y)) = queue.pop_front() {
+        for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+            if passable(next.0, next.1) && reached.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let mut unreachable = Vec::new();
+    for y in 0..data.height() as i32 {
+        for x in 0..data.width() as i32 {
+            if !data.is_solid(legend, x, y) && !reached.contains(&(x, y)) {
+                unreachable.push((x, y));
+            }
+        }
+    }
+    if !unreachable.is_empty() {
+        let listed: Vec<String> = unreachable
+            .iter()
+            .take(MAX_LISTED)
+            .map(|tile| format!("{:?}", tile))
+            .collect();
+        let more = unreachable.len().saturating_sub(MAX_LISTED);
+        report.error(format!(
+            "{} walkable tiles can't be reached from any spawn: {}{}",
+            unreachable.len(),
+            listed.join(", "),
+            if more > 0 {
+                format!(" and {} more", more)
+            } else {
+                String::new()
+            }
+        ));
+    }
+}
+
+fn check_warps(file: &Path, legend: &Legend, objects: &ObjectsFile, report: &mut Report) {
+    let dir = file.parent().unwrap_or_else(|| Path::new(MAPS_DIR));
+    for (tile, warp) in objects.warps.0.iter() {
+        match spawns_of(dir, legend, &warp.map) {
+            None => report.error(format!(
+                "warp at {:?} leads to map {}, which doesn't exist",
+                tile, warp.map
+            )),
+            Some(spawns) if !spawns.contains(&warp.spawn) => report.error(format!(
+                "warp at {:?} leads to spawn {} in {}, which doesn't exist",
+                tile, warp.spawn, warp.map
+            )),
+            Some(_) => {}
+        }
+    }
+}
+
+//Spawn names of a map the way the game looks it up, None when there is no such map
+fn spawns_of(dir: &Path, legend: &Legend, name: &str) -> Option<Vec<String>> {
+    if let Some((seed, floor)) = dungeon::parse_map_name(name) {
+        let generated = dungeon::generate(seed, floor, &DungeonSettings::default());
+        return Some(
+            generated
+                .spawns()
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+        );
+    }
+
+    let map = dir.join(format!("{}.map", name));
+    if map.exists() {
+        //A broken target is reported when that map itself is checked
+        let data = fs::read_to_string(&map)
+            .ok()
+            .and_then(|contents| MapData::parse(&contents).ok())
+            .unwrap_or_default();
+        let objects: ObjectsFile = fs::read_to_string(map.with_extension("ron"))
+            .ok()
+            .and_then(|contents| ron::from_str(&contents).ok())
+            .unwrap_or_default();
+        let spawns = spawn_points(&data, legend, &objects);
+        return Some(spawns.into_iter().map(|(name, _)| name).collect());
+    }
+
+    for extension in ["tmj", "tmx"] {
+        let path = dir.join(format!("{}.{}", name, extension));
+        if path.exists() {
+            let tiled = TiledMap::load(&path).ok()?;
+            let tiles = tiled.import_tiles();
+            let spawns = spawn_points(&tiles.data, &tiles.legend, &tiled_objects(&tiled));
+            return Some(spawns.into_iter().map(|(name, _)| name).collect());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEGEND: &str = r#"{
+        '#': (name: "Wall", color: (0.0, 0.0, 0.0), collider: true),
+        '.': (name: "Floor", color: (1.0, 1.0, 1.0)),
+        '+': (name: "Door", color: (1.0, 1.0, 1.0), collider: true, object: Some(Door)),
+        '$': (name: "Spawn", color: (1.0, 1.0, 1.0), object: Some(Spawn)),
+    }"#;
+
+    //Runs the checks every map gets, warp targets are looked up in a directory that doesn't exist
+    fn errors(contents: &str, objects: &str) -> Vec<String> {
+        let legend = Legend::from_ron(LEGEND).unwrap();
+        let data = MapData::parse(contents).unwrap();
+        let objects = ron::from_str(objects).unwrap();
+        let mut report = Report {
+            file: PathBuf::from("test.map"),
+            errors: Vec::new(),
+        };
+        let file = Path::new("missing/test.map");
+        check_layout(file, &data, &legend, &objects, &mut report);
+        report.errors
+    }
+
+    #[test]
+    fn closed_map_passes() {
+        let map = "[ground]\n####\n#..#\n####\n\n[objects]\n\n $\n";
+        assert!(errors(map, "()").is_empty());
+    }
+
+    #[test]
+    fn reports_open_edges() {
+        let map = "[ground]\n####\n#...\n####\n\n[objects]\n\n $\n";
+        let found = errors(map, "()");
+        assert_eq!(
+            found,
+            vec!["map is not enclosed, (3, 1) is walkable on the edge"]
+        );
+    }
+
+    #[test]
+    fn warps_may_sit_on_the_edge() {
+        let map = "[ground]\n####\n#...\n####\n\n[objects]\n\n $\n";
+        let objects = format!(
+            "(warps: {{(3, 1): (map: \"{}\", spawn: \"default\")}})",
+            dungeon::map_name(1, 1)
+        );
+        assert!(errors(map, &objects).is_empty());
+    }
+
+    #[test]
+    fn reports_missing_and_duplicate_spawns() {
+        let map = "[ground]\n####\n#..#\n####\n";
+        let found = errors(map, "()");
+        assert!(found[0].contains("no spawn points"), "{:?}", found);
+
+        let objects = "(spawns: {\"default\": (1, 1), \"default\": (2, 1)})";
+        let found = errors(map, objects);
+        assert_eq!(found, vec!["spawn default is defined twice"]);
+    }
+
+    #[test]
+    fn reports_unreachable_tiles() {
+        let map = "[ground]\n#####\n#.#.#\n#####\n\n[objects]\n\n $\n";
+        let found = errors(map, "()");
+        assert_eq!(
+            found,
+            vec!["1 walkable tiles can't be reached from any spawn: (3, 1)"]
+        );
+    }
+
+    #[test]
+    fn doors_and_switched_walls_can_be_passed() {
+        let map = "[ground]\n#######\n#.+.#.#\n#######\n\n[objects]\n\n $\n";
+        let objects = "(switches: {(1, 1): [(4, 1)]})";
+        assert!(errors(map, objects).is_empty());
+    }
+
+    #[test]
+    fn reports_broken_warp_targets() {
+        let map = "[ground]\n####\n#..#\n####\n\n[objects]\n\n $\n";
+        let objects = format!(
+            "(warps: {{(2, 1): (map: \"{}\", spawn: \"nowhere\"), (1, 1): (map: \"attic\", spawn: \"default\")}})",
+            dungeon::map_name(1, 1)
+        );
+        let found = errors(map, &objects);
+        assert_eq!(found.len(), 2, "{:?}", found);
+        assert!(found[0].contains("spawn nowhere"));
+        assert!(found[1].contains("map attic"));
+    }
+
+    #[test]
+    fn reports_ragged_ground_rows() {
+        let mut report = Report {
+            file: PathBuf::from("test.map"),
+            errors: Vec::new(),
+        };
+        check_ragged_rows("[ground]\n###\n#.\n###\n\n[objects]\n\n", &mut report);
+        assert_eq!(
+            report.errors,
+            vec!["line 3: ground row is 2 tiles wide, the map is 3"]
+        );
+    }
+
+    #[test]
+    fn reads_tiled_objects() {
+        let tmj = r#"{
+            "width": 2, "height": 2, "tilewidth": 16, "tileheight": 16,
+            "layers": [{"type": "objectgroup", "name": "objects", "objects": [
+                {"name": "", "type": "spawn", "x": 0, "y": 0},
+                {"name": "out", "type": "warp", "x": 0, "y": 16, "width": 32, "height": 16,
+                 "properties": [
+                    {"name": "map", "type": "string", "value": "town"},
+                    {"name": "spawn", "type": "string", "value": "meadow"}
+                 ]}
+            ]}]
+        }"#;
+        let tiled = TiledMap::parse(tmj, Path::new("test.tmj"), &Default::default()).unwrap();
+        let objects = tiled_objects(&tiled);
+        assert_eq!(objects.spawns.0, vec![(DEFAULT_SPAWN.to_string(), (0, 0))]);
+        let warps: Vec<_> = objects.warps.0.iter().map(|(tile, _)| *tile).collect();
+        assert_eq!(warps, vec![(0, 1), (1, 1)]);
+        assert_eq!(objects.warps.0[0].1.map, "town");
+    }
+
+    #[test]
+    fn skips_objects_files() {
+        assert!(is_map_file(Path::new("maps/meadow.tmj")));
+        assert!(!is_map_file(Path::new("maps/town.ron")));
+    }
+}
@@ -0,0 +1,249 @@
+use bevy::{prelude::*, render::camera::Camera2d, transform::TransformSystem};
+use bevy_2d_rpg::collision::CollisionMap;
+
+use crate::{
+    input::{InputAction, InputActions},
+    player::Player,
+    GameState, RESOLUTION, TILE_SIZE,
+};
+
+pub struct CameraPlugin;
+
+pub struct CameraController {
+    //Where the camera looks, before any shake is added
+    pub position: Vec2,
+    //How quickly the camera catches up with the player, higher is snappier
+    pub follow_speed: f32,
+    pub zoom_levels: Vec<f32>,
+    pub zoom_index: usize,
+    //Layered on top of the position every frame, never stored into it
+    pub shake_offset: Vec2,
+    //Followed instead of the player while set, the map editor pans with it
+    pub free_look: Option<Vec2>,
+    saved_overworld_position: Option<Vec2>,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        CameraController {
+            position: Vec2::ZERO,
+            follow_speed: 6.0,
+            zoom_levels: vec![0.5, 0.75, 1.0, 1.5],
+            zoom_index: 2,
+            shake_offset: Vec2::ZERO,
+            free_look: None,
+            saved_overworld_position: None,
+        }
+    }
+}
+
+impl CameraController {
+    pub fn zoom(&self) -> f32 {
+        self.zoom_levels[self.zoom_index]
+    }
+
+    //Where a point of the window lands in the world, the window's corners are -1 and 1
+    pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+        self.position + screen * Vec2::new(RESOLUTION, 1.0) * self.zoom()
+    }
+
+    //Places something at a fixed spot on screen, offset is in unzoomed screen units
+    pub fn screen_transform(&self, offset: Vec2, z: f32) -> Transform {
+        Transform {
+            translation: (self.position + offset * self.zoom()).extend(z),
+            scale: Vec3::splat(self.zoom()),
+            ..Default::default()
+        }
+    }
+
+    fn clamp_to_map(&mut self, collision_map: &CollisionMap) {
+        let half_view = Vec2::new(RESOLUTION, 1.0) * self.zoom();
+        let map_min = Vec2::new(
+            -0.5 * TILE_SIZE,
+            -(collision_map.height() as f32 - 0.5) * TILE_SIZE,
+        );
+        let map_max = Vec2::new(
+            (collision_map.width() as f32 - 0.5) * TILE_SIZE,
+            0.5 * TILE_SIZE,
+        );
+
+        //Maps smaller than the screen are centered instead
+        self.position.x = if map_max.x - map_min.x <= half_view.x * 2.0 {
+            (map_min.x + map_max.x) / 2.0
+        } else {
+            self.position
+                .x
+                .clamp(map_min.x + half_view.x, map_max.x - half_view.x)
+        };
+        self.position.y = if map_max.y - map_min.y <= half_view.y * 2.0 {
+            (map_min.y + map_max.y) / 2.0
+        } else {
+            self.position
+                .y
+                .clamp(map_min.y + half_view.y, map_max.y - half_view.y)
+        };
+    }
+}
+
+//Trauma based shake, anything can add trauma and it decays on its own
+pub struct ScreenShake {
+    trauma: f32,
+    //Trauma lost per second
+    pub decay: f32,
+    //Offset at full trauma, in world units
+    pub max_offset: f32,
+    //How fast the noise is scrolled through, higher is more jittery
+    pub frequency: f32,
+    //Global setting, 0.0 turns shaking off
+    pub intensity: f32,
+    time: f32,
+}
+
+impl Default for ScreenShake {
+    fn default() -> Self {
+        ScreenShake {
+            trauma: 0.0,
+            decay: 1.2,
+            max_offset: 0.15,
+            frequency: 25.0,
+            intensity: 1.0,
+            time: 0.0,
+        }
+    }
+}
+
+impl ScreenShake {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    //Squaring the trauma makes small hits subtle and big ones violent
+    fn offset(&self) -> Vec2 {
+        let strength = self.trauma * self.trauma * self.intensity * self.max_offset;
+        let t = self.time * self.frequency;
+        Vec2::new(value_noise(t, 0), value_noise(t, 1)) * strength
+    }
+}
+
+fn noise_hash(n: i32, seed: u32) -> f32 {
+    let mut h = (n as u32).wrapping_mul(0x27d4_eb2d) ^ seed.wrapping_mul(0x1656_67b1);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    (h as f32 / u32::MAX as f32) * 2.0 - 1.0
+}
+
+//Smoothly interpolated 1d noise in -1..1, each seed gives an unrelated curve
+fn value_noise(x: f32, seed: u32) -> f32 {
+    let cell = x.floor();
+    let t = x - cell;
+    let a = noise_hash(cell as i32, seed);
+    let b = noise_hash(cell as i32 + 1, seed);
+    let smooth = t * t * (3.0 - 2.0 * t);
+    a + (b - a) * smooth
+}
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraController>()
+            .init_resource::<ScreenShake>()
+            .add_system(update_screen_shake)
+            .add_system_set(
+                SystemSet::on_update(GameState::Overworld)
+                    .with_system(snap_to_new_player)
+                    .with_system(camera_follow.after(snap_to_new_player)),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Combat).with_system(save_overworld_position),
+            )
+            .add_system_set(SystemSet::on_update(GameState::Combat).with_system(combat_camera))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Combat).with_system(restore_overworld_position),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                apply_camera.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+//Avoids panning across the map when the player first appears
+fn snap_to_new_player(
+    mut camera: ResMut<CameraController>,
+    player_query: Query<&Transform, Added<Player>>,
+    collision_map: Res<CollisionMap>,
+) {
+    if let Ok(player_transform) = player_query.get_single() {
+        camera.position = player_transform.translation.truncate();
+        camera.clamp_to_map(&collision_map);
+    }
+}
+
+fn camera_follow(
+    mut camera: ResMut<CameraController>,
+    player_query: Query<&Transform, With<Player>>,
+    collision_map: Res<CollisionMap>,
+    actions: Res<InputActions>,
+    time: Res<Time>,
+) {
+    if actions.just_pressed(InputAction::ZoomIn) && camera.zoom_index > 0 {
+        camera.zoom_index -= 1;
+    }
+    if actions.just_pressed(InputAction::ZoomOut)
+        && camera.zoom_index + 1 < camera.zoom_levels.len()
+    {
+        camera.zoom_index += 1;
+    }
+
+    let target = match camera.free_look {
+        Some(position) => position,
+        None => player_query.single().translation.truncate(),
+    };
+    //Frame rate independent damping
+    let blend = 1.0 - f32::exp(-camera.follow_speed * time.delta_seconds());
+    camera.position = camera.position.lerp(target, blend);
+    camera.clamp_to_map(&collision_map);
+}
+
+fn save_overworld_position(mut camera: ResMut<CameraController>) {
+    camera.saved_overworld_position = Some(camera.position);
+}
+
+//Combat ui is laid out around the origin at the default zoom
+fn combat_camera(mut camera: ResMut<CameraController>) {
+    camera.position = Vec2::ZERO;
+}
+
+fn restore_overworld_position(mut camera: ResMut<CameraController>) {
+    if let Some(position) = camera.saved_overworld_position.take() {
+        camera.position = position;
+    }
+}
+
+fn update_screen_shake(
+    mut shake: ResMut<ScreenShake>,
+    mut camera: ResMut<CameraController>,
+    time: Res<Time>,
+) {
+    shake.time += time.delta_seconds();
+    shake.trauma = (shake.trauma - shake.decay * time.delta_seconds()).max(0.0);
+    camera.shake_offset = shake.offset();
+}
+
+fn apply_camera(
+    camera: Res<CameraController>,
+    state: Res<State<GameState>>,
+    mut camera_query: Query<&mut Transform, With<Camera2d>>,
+) {
+    let zoom = if state.current() == &GameState::Combat {
+        1.0
+    } else {
+        camera.zoom()
+    };
+
+    let mut camera_transform = camera_query.single_mut();
+    let position = camera.position + camera.shake_offset;
+    camera_transform.translation.x = position.x;
+    camera_transform.translation.y = position.y;
+    camera_transform.scale = Vec3::new(zoom, zoom, 1.0);
+}
@@ -0,0 +1,146 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{player::Player, GameState};
+
+pub struct ClockPlugin;
+
+//Real seconds for one in-game hour, a full day takes 8 minutes
+const SECONDS_PER_HOUR: f32 = 20.0;
+//Strongest the night overlay gets, 1.0 would be pitch black
+const MAX_DARKNESS: f32 = 0.55;
+//Above the map and characters, textboxes have to be drawn higher than this
+const NIGHT_OVERLAY_Z: f32 = 950.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TimeOfDay {
+    Morning,
+    Day,
+    Evening,
+    Night,
+}
+
+//Only runs while the Overworld is the active state, so menus and fights stop it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameClock {
+    pub day: u32,
+    //0.0 up to 24.0
+    pub hour: f32,
+    //For anything else that needs to freeze time, like cutscenes
+    #[serde(skip)]
+    pub paused: bool,
+}
+
+#[derive(Component)]
+struct NightOverlay;
+
+impl Default for GameClock {
+    fn default() -> Self {
+        GameClock {
+            day: 0,
+            hour: 8.0,
+            paused: false,
+        }
+    }
+}
+
+impl GameClock {
+    pub fn time_of_day(&self) -> TimeOfDay {
+        match self.hour {
+            h if (5.0..10.0).contains(&h) => TimeOfDay::Morning,
+            h if (10.0..18.0).contains(&h) => TimeOfDay::Day,
+            h if (18.0..21.0).contains(&h) => TimeOfDay::Evening,
+            _ => TimeOfDay::Night,
+        }
+    }
+
+    //0.0 in daylight, 1.0 in the dead of night, fading over dusk and dawn
+    pub fn darkness(&self) -> f32 {
+        match self.hour {
+            h if h < 5.0 => 1.0,
+            h if h < 7.0 => 1.0 - (h - 5.0) / 2.0,
+            h if h < 18.0 => 0.0,
+            h if h < 21.0 => (h - 18.0) / 3.0,
+            _ => 1.0,
+        }
+    }
+
+    fn advance(&mut self, hours: f32) {
+        self.hour += hours;
+        while self.hour >= 24.0 {
+            self.hour -= 24.0;
+            self.day += 1;
+        }
+    }
+
+    //Warm at dusk, deep blue at night
+    fn tint(&self) -> Color {
+        let darkness = self.darkness();
+        let dusk = Vec3::new(0.6, 0.3, 0.2);
+        let night = Vec3::new(0.05, 0.05, 0.25);
+        let color = dusk.lerp(night, darkness);
+        Color::rgba(color.x, color.y, color.z, darkness * MAX_DARKNESS)
+    }
+}
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Overworld).with_system(spawn_night_overlay),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Overworld)
+                .with_system(advance_clock)
+                .with_system(update_night_overlay.after(advance_clock)),
+        )
+        .add_system_set(SystemSet::on_pause(GameState::Overworld).with_system(hide_overlay))
+        .add_system_set(SystemSet::on_resume(GameState::Overworld).with_system(show_overlay));
+    }
+}
+
+fn advance_clock(mut clock: ResMut<GameClock>, player_query: Query<&Player>, time: Res<Time>) {
+    //Reading a textbox doesn't cost any daylight
+    let player_active = player_query
+        .get_single()
+        .map_or(true, |player| player.active);
+    if clock.paused || !player_active {
+        return;
+    }
+    clock.advance(time.delta_seconds() / SECONDS_PER_HOUR);
+}
+
+fn spawn_night_overlay(mut commands: Commands, clock: Res<GameClock>) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: clock.tint(),
+                custom_size: Some(Vec2::splat(100000.0)),
+                ..Default::default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, NIGHT_OVERLAY_Z),
+            ..Default::default()
+        })
+        .insert(NightOverlay)
+        .insert(Name::new("Night Overlay"));
+}
+
+fn update_night_overlay(
+    clock: Res<GameClock>,
+    mut overlay_query: Query<&mut Sprite, With<NightOverlay>>,
+) {
+    for mut sprite in overlay_query.iter_mut() {
+        sprite.color = clock.tint();
+    }
+}
+
+fn hide_overlay(mut overlay_query: Query<&mut Visibility, With<NightOverlay>>) {
+    for mut visibility in overlay_query.iter_mut() {
+        visibility.is_visible = false;
+    }
+}
+
+fn show_overlay(mut overlay_query: Query<&mut Visibility, With<NightOverlay>>) {
+    for mut visibility in overlay_query.iter_mut() {
+        visibility.is_visible = true;
+    }
+}
@@ -0,0 +1,154 @@
+use bevy::math::Vec2;
+
+//Tiles are one unit wide and centered on their coordinates, y grows downwards like map rows
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct TileFlags {
+    pub solid: bool,
+    pub encounter_zone: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct CollisionMap {
+    width: usize,
+    height: usize,
+    tiles: Vec<TileFlags>,
+}
+
+impl CollisionMap {
+    pub fn new(width: usize, height: usize) -> Self {
+        CollisionMap {
+            width,
+            height,
+            tiles: vec![TileFlags::default(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        Some(y as usize * self.width + x as usize)
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<TileFlags> {
+        self.index(x, y).map(|i| self.tiles[i])
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, flags: TileFlags) {
+        if let Some(i) = self.index(x, y) {
+            self.tiles[i] = flags;
+        }
+    }
+
+    //Keeps the encounter zone, used by anything standing on or freeing a tile
+    pub fn set_solid(&mut self, x: i32, y: i32, solid: bool) {
+        if let Some(i) = self.index(x, y) {
+            self.tiles[i].solid = solid;
+        }
+    }
+
+    //Anything outside of the map is treated as a wall
+    pub fn is_solid(&self, x: i32, y: i32) -> bool {
+        self.get(x, y).is_none_or(|tile| tile.solid)
+    }
+
+    pub fn encounter_zone(&self, x: i32, y: i32) -> Option<usize> {
+        self.get(x, y).and_then(|tile| tile.encounter_zone)
+    }
+
+    //Only the handful of tiles under the box are looked at
+    fn overlapped_tiles(center: Vec2, half_size: f32) -> impl Iterator<Item = (i32, i32)> {
+        let min_x = (center.x - half_size + 0.5).floor() as i32;
+        let max_x = (center.x + half_size - 0.5).ceil() as i32;
+        let min_y = (center.y - half_size + 0.5).floor() as i32;
+        let max_y = (center.y + half_size - 0.5).ceil() as i32;
+        (min_y..=max_y).flat_map(move |y| (min_x..=max_x).map(move |x| (x, y)))
+    }
+
+    pub fn overlaps_solid(&self, center: Vec2, half_size: f32) -> bool {
+        Self::overlapped_tiles(center, half_size).any(|(x, y)| self.is_solid(x, y))
+    }
+
+    pub fn overlapped_encounter_zone(&self, center: Vec2, half_size: f32) -> Option<usize> {
+        Self::overlapped_tiles(center, half_size).find_map(|(x, y)| self.encounter_zone(x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles(center: (f32, f32), half_size: f32) -> Vec<(i32, i32)> {
+        CollisionMap::overlapped_tiles(Vec2::new(center.0, center.1), half_size).collect()
+    }
+
+    #[test]
+    fn box_filling_a_tile_only_touches_that_tile() {
+        assert_eq!(tiles((2.0, 3.0), 0.5), vec![(2, 3)]);
+    }
+
+    #[test]
+    fn box_on_a_tile_edge_overlaps_both_sides() {
+        assert_eq!(tiles((2.5, 3.0), 0.4), vec![(2, 3), (3, 3)]);
+        assert_eq!(tiles((2.0, 3.5), 0.4), vec![(2, 3), (2, 4)]);
+    }
+
+    #[test]
+    fn box_spanning_several_tiles() {
+        let overlapped = tiles((1.0, 1.0), 1.0);
+        assert_eq!(overlapped.len(), 9);
+        for y in 0..=2 {
+            for x in 0..=2 {
+                assert!(overlapped.contains(&(x, y)));
+            }
+        }
+    }
+
+    #[test]
+    fn negative_coordinates() {
+        assert_eq!(tiles((-1.0, -2.0), 0.4), vec![(-1, -2)]);
+        assert_eq!(tiles((-0.5, 0.0), 0.4), vec![(-1, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn solid_tiles() {
+        let mut map = CollisionMap::new(3, 2);
+        map.set_solid(1, 0, true);
+        assert!(map.is_solid(1, 0));
+        assert!(!map.is_solid(0, 0));
+        assert!(map.is_solid(-1, 0));
+        assert!(map.is_solid(0, 2));
+        assert!(map.overlaps_solid(Vec2::new(0.5, 0.0), 0.4));
+        assert!(!map.overlaps_solid(Vec2::new(0.0, 0.5), 0.4));
+        //Hanging off the edge of the map
+        assert!(map.overlaps_solid(Vec2::new(2.5, 1.0), 0.4));
+    }
+
+    #[test]
+    fn set_solid_keeps_the_encounter_zone() {
+        let mut map = CollisionMap::new(2, 2);
+        map.set(
+            1,
+            1,
+            TileFlags {
+                solid: false,
+                encounter_zone: Some(3),
+            },
+        );
+        map.set_solid(1, 1, true);
+        assert!(map.is_solid(1, 1));
+        assert_eq!(map.encounter_zone(1, 1), Some(3));
+        assert_eq!(
+            map.overlapped_encounter_zone(Vec2::new(0.5, 0.5), 0.4),
+            Some(3)
+        );
+    }
+}
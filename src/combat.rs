@@ -1,14 +1,17 @@
-use bevy::{prelude::*, render::camera::Camera2d};
+use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
 use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     ascii::{
         spawn_ascii_sprite, spawn_ascii_text, spawn_nine_slice, AsciiSheet, AsciiText, NineSlice,
-        NineSliceIndices,
     },
+    camera::ScreenShake,
+    encounter::ActiveEncounter,
     fadeout::create_fadeout,
     graphics::{spawn_enemy_sprite, CharacterSheet},
+    input::{InputAction, InputActions},
     player::Player,
     GameState, RESOLUTION, TILE_SIZE,
 };
@@ -25,9 +28,10 @@ pub struct CombatStats {
 #[derive(Component)]
 pub struct Enemy {
     enemy_type: EnemyType,
+    level: usize,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum EnemyType {
     Bat,
     Ghost,
@@ -54,8 +58,8 @@ pub enum CombatState {
 pub struct AttackEffects {
     timer: Timer,
     flash_speed: f32,
-    screen_shake_amount: f32,
-    current_shake: f32,
+    //Trauma added to the ScreenShake when the enemy hits
+    screen_shake_trauma: f32,
 }
 
 #[derive(Component)]
@@ -68,8 +72,7 @@ impl Plugin for CombatPlugin {
             .insert_resource(AttackEffects {
                 timer: Timer::from_seconds(0.7, true),
                 flash_speed: 0.1,
-                screen_shake_amount: 0.1,
-                current_shake: 0.0,
+                screen_shake_trauma: 0.6,
             })
             //TODO reset
             .insert_resource(CombatMenuSelection {
@@ -81,7 +84,6 @@ impl Plugin for CombatPlugin {
             .add_system_set(
                 SystemSet::on_update(GameState::Combat)
                     .with_system(combat_input)
-                    .with_system(combat_camera)
                     .with_system(highlight_combat_buttons)
                     .with_system(combat_damage_calc),
             )
@@ -111,6 +113,9 @@ impl Plugin for CombatPlugin {
             )
             .add_system_set(
                 SystemSet::on_update(CombatState::EnemyAttack).with_system(handle_attack_effects),
+            )
+            .add_system_set(
+                SystemSet::on_enter(CombatState::EnemyAttack).with_system(shake_on_enemy_attack),
             );
     }
 }
@@ -118,10 +123,10 @@ impl Plugin for CombatPlugin {
 fn handle_accepting_reward(
     mut commands: Commands,
     ascii: Res<AsciiSheet>,
-    keyboard: Res<Input<KeyCode>>,
+    actions: Res<InputActions>,
     mut combat_state: ResMut<State<CombatState>>,
 ) {
-    if keyboard.just_pressed(KeyCode::E) {
+    if actions.just_pressed(InputAction::Confirm) {
         combat_state.set(CombatState::Exiting).unwrap();
         create_fadeout(&mut commands, None, &ascii);
     }
@@ -129,19 +134,19 @@ fn handle_accepting_reward(
 
 fn give_reward(
     mut commands: Commands,
-    ascii: Res<AsciiSheet>,
     mut player_query: Query<(&mut Player, &mut CombatStats)>,
-    mut keyboard: ResMut<Input<KeyCode>>,
+    enemy_query: Query<&Enemy>,
+    mut actions: ResMut<InputActions>,
 ) {
-    keyboard.clear();
-    let exp_reward = match enemy_query.single().enemy_type {
+    actions.clear();
+    let enemy = enemy_query.single();
+    let exp_reward = match enemy.enemy_type {
         EnemyType::Bat => 10,
         EnemyType::Ghost => 30,
-    };
+    } * enemy.level;
     let reward_text = format!("Earned: {} exp", exp_reward);
     let text = spawn_ascii_text(
         &mut commands,
-        &ascii,
         &reward_text,
         Vec3::new(-((reward_text.len() / 2) as f32 * TILE_SIZE), 0.0, 0.0),
     );
@@ -151,7 +156,6 @@ fn give_reward(
         let level_text = "Level up !";
         let text = spawn_ascii_text(
             &mut commands,
-            &ascii,
             level_text,
             Vec3::new(
                 -((level_text.len() / 2) as f32 * TILE_SIZE),
@@ -171,14 +175,12 @@ fn despawn_all_combat_text(mut commands: Commands, text_query: Query<Entity, Wit
 
 fn spawn_player_health(
     mut commands: Commands,
-    ascii: Res<AsciiSheet>,
     player_query: Query<(Entity, &CombatStats, &Transform), With<Player>>,
 ) {
     let (player, stats, transform) = player_query.single();
     let health_text = format!("Health: {}", stats.health);
     let text = spawn_ascii_text(
         &mut commands,
-        &ascii,
         &health_text,
         Vec3::new(-RESOLUTION + TILE_SIZE, -1.0 + TILE_SIZE, 0.0) - transform.translation,
     );
@@ -201,9 +203,6 @@ fn handle_attack_effects(
         } else {
             enemy_sprite.is_visible = true;
         }
-    } else {
-        attack_fx.current_shake = attack_fx.screen_shake_amount
-            * f32::sin(attack_fx.timer.percent() * 2.0 * std::f32::consts::PI);
     }
 
     if attack_fx.timer.just_finished() {
@@ -216,6 +215,10 @@ fn handle_attack_effects(
     }
 }
 
+fn shake_on_enemy_attack(attack_fx: Res<AttackEffects>, mut shake: ResMut<ScreenShake>) {
+    shake.add_trauma(attack_fx.screen_shake_trauma);
+}
+
 fn set_starting_state(mut state: ResMut<State<CombatState>>) {
     let _ = state.set(CombatState::PlayerTurn);
 }
@@ -256,20 +259,19 @@ fn despawn_menu(mut commands: Commands, button_query: Query<Entity, With<CombatM
 fn highlight_combat_buttons(
     menu_state: Res<CombatMenuSelection>,
     button_query: Query<(&Children, &CombatMenuOption)>,
-    nine_slice_query: Query<&Children, With<NineSlice>>,
-    mut sprites_query: Query<&mut TextureAtlasSprite>,
+    mut nine_slice_query: Query<&mut NineSlice>,
 ) {
     for (button_children, button_id) in button_query.iter() {
+        let color = if menu_state.selected == *button_id {
+            Color::RED
+        } else {
+            Color::WHITE
+        };
         for button_child in button_children.iter() {
-            if let Ok(nine_slice_children) = nine_slice_query.get(*button_child) {
-                for nine_slice_child in nine_slice_children.iter() {
-                    if let Ok(mut sprite) = sprites_query.get_mut(*nine_slice_child) {
-                        if menu_state.selected == *button_id {
-                            sprite.color = Color::RED;
-                        } else {
-                            sprite.color = Color::WHITE;
-                        }
-                    }
+            if let Ok(mut nine_slice) = nine_slice_query.get_mut(*button_child) {
+                //Only touched on a change so the mesh isn't rebuilt every frame
+                if nine_slice.color != color {
+                    nine_slice.color = color;
                 }
             }
         }
@@ -278,17 +280,15 @@ fn highlight_combat_buttons(
 
 fn spawn_combat_button(
     commands: &mut Commands,
-    ascii: &AsciiSheet,
-    indices: &NineSliceIndices,
     translation: Vec3,
     text: &str,
     id: CombatMenuOption,
     size: Vec2,
 ) -> Entity {
-    let fight_nine_slice = spawn_nine_slice(commands, ascii, indices, size.x, size.y);
+    let fight_nine_slice = spawn_nine_slice(commands, size.x, size.y);
 
     let x_offset = (-size.x / 2.0 + 1.5) * TILE_SIZE;
-    let fight_text = spawn_ascii_text(commands, ascii, text, Vec3::new(x_offset, 0.0, 0.0));
+    let fight_text = spawn_ascii_text(commands, text, Vec3::new(x_offset, 0.0, 0.0));
 
     commands
         .spawn()
@@ -304,11 +304,7 @@ fn spawn_combat_button(
         .id()
 }
 
-fn spawn_combat_menu(
-    mut commands: Commands,
-    ascii: Res<AsciiSheet>,
-    nine_slice_indices: Res<NineSliceIndices>,
-) {
+fn spawn_combat_menu(mut commands: Commands) {
     let box_height = 3.0;
     let box_center_y = -1.0 + box_height * TILE_SIZE / 2.0;
 
@@ -318,8 +314,6 @@ fn spawn_combat_menu(
 
     spawn_combat_button(
         &mut commands,
-        &ascii,
-        &nine_slice_indices,
         Vec3::new(run_center_x, box_center_y, 100.0),
         run_text,
         CombatMenuOption::Run,
@@ -332,8 +326,6 @@ fn spawn_combat_menu(
 
     spawn_combat_button(
         &mut commands,
-        &ascii,
-        &nine_slice_indices,
         Vec3::new(fight_center_x, box_center_y, 100.0),
         fight_text,
         CombatMenuOption::Fight,
@@ -342,12 +334,10 @@ fn spawn_combat_menu(
 }
 
 fn combat_damage_calc(
-    mut commands: Commands,
     mut fight_event: EventReader<FightEvent>,
     //Not necssacarily enemy
     mut enemy_query: Query<(&Children, &mut CombatStats)>,
-    ascii: Res<AsciiSheet>,
-    text_query: Query<&Transform, With<CombatText>>,
+    mut text_query: Query<&mut AsciiText, With<CombatText>>,
     mut combat_state: ResMut<State<CombatState>>,
 ) {
     if let Some(fight_event) = fight_event.iter().next() {
@@ -356,28 +346,15 @@ fn combat_damage_calc(
             .get_mut(fight_event.target)
             .expect("Fighting enemy without stats");
 
-        //Damage calc
-        stats.health = std::cmp::max(
-            stats.health - (fight_event.damage_amount - stats.defense),
-            0,
-        );
+        //Damage calc, every hit lands for at least 1 so defense never heals or stalls a fight
+        let damage = std::cmp::max(fight_event.damage_amount - stats.defense, 1);
+        stats.health = std::cmp::max(stats.health - damage, 0);
 
         //Update health
         for child in target_children.iter() {
             //See if this child is the health text
-            if let Ok(transform) = text_query.get(*child) {
-                //Delete old text
-                commands.entity(*child).despawn_recursive();
-                //Create new text
-                let new_health = spawn_ascii_text(
-                    &mut commands,
-                    &ascii,
-                    &format!("Health: {}", stats.health as usize),
-                    //relative to enemy pos
-                    transform.translation,
-                );
-                commands.entity(new_health).insert(CombatText);
-                commands.entity(fight_event.target).add_child(new_health);
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                text.text = format!("Health: {}", stats.health as usize);
             }
         }
 
@@ -393,7 +370,7 @@ fn combat_damage_calc(
 
 fn combat_input(
     mut commands: Commands,
-    keyboard: Res<Input<KeyCode>>,
+    actions: Res<InputActions>,
     player_query: Query<&CombatStats, With<Player>>,
     enemy_query: Query<Entity, With<Enemy>>,
     mut fight_event: EventWriter<FightEvent>,
@@ -410,10 +387,10 @@ fn combat_input(
     //TODO handle multiple enemies
     let enemy = enemy_query.single();
     let mut new_selection = menu_state.selected as isize;
-    if keyboard.just_pressed(KeyCode::A) {
+    if actions.just_pressed(InputAction::MenuLeft) {
         new_selection -= 1;
     }
-    if keyboard.just_pressed(KeyCode::D) {
+    if actions.just_pressed(InputAction::MenuRight) {
         new_selection += 1;
     }
     new_selection = (new_selection + NUM_MENU_OPTIONS) % NUM_MENU_OPTIONS;
@@ -424,7 +401,7 @@ fn combat_input(
         _ => unreachable!("Bad menu selection"),
     };
 
-    if keyboard.just_pressed(KeyCode::E) {
+    if actions.just_pressed(InputAction::Confirm) {
         match menu_state.selected {
             CombatMenuOption::Fight => fight_event.send(FightEvent {
                 //TODO select enemy and attack type
@@ -440,21 +417,19 @@ fn combat_input(
     }
 }
 
-fn combat_camera(
-    mut camera_query: Query<&mut Transform, With<Camera2d>>,
-    attack_fx: Res<AttackEffects>,
+fn spawn_enemy(
+    mut commands: Commands,
+    characters: Res<CharacterSheet>,
+    active_encounter: Option<Res<ActiveEncounter>>,
 ) {
-    let mut camera_transform = camera_query.single_mut();
-    camera_transform.translation.x = attack_fx.current_shake;
-    camera_transform.translation.y = 0.0;
-}
-
-fn spawn_enemy(mut commands: Commands, ascii: Res<AsciiSheet>, characters: Res<CharacterSheet>) {
-    let enemy_type = match rand::random::<f32>() {
-        x if x < 0.5 => EnemyType::Bat,
-        _ => EnemyType::Ghost,
+    let (enemy_type, level) = match active_encounter {
+        Some(encounter) => (encounter.enemy, encounter.level),
+        None => match rand::random::<f32>() {
+            x if x < 0.5 => (EnemyType::Bat, 1),
+            _ => (EnemyType::Ghost, 1),
+        },
     };
-    let stats = match enemy_type {
+    let mut stats = match enemy_type {
         EnemyType::Bat => {
             let mut health = thread_rng().gen_range(1..=6);
             let mut attack = thread_rng().gen_range(1..=3);
@@ -481,9 +456,15 @@ fn spawn_enemy(mut commands: Commands, ascii: Res<AsciiSheet>, characters: Res<C
         }
     };
 
+    //Every level above the first makes the enemy a bit tougher
+    let bonus = level as isize - 1;
+    stats.max_health += 2 * bonus;
+    stats.health = stats.max_health;
+    stats.attack += bonus;
+    stats.defense += bonus / 2;
+
     let health_text = spawn_ascii_text(
         &mut commands,
-        &ascii,
         &format!("Health: {}", stats.health as usize),
         //relative to enemy pos
         Vec3::new(-4.5 * TILE_SIZE, 2.0 * TILE_SIZE, 100.0),
@@ -494,14 +475,15 @@ fn spawn_enemy(mut commands: Commands, ascii: Res<AsciiSheet>, characters: Res<C
         &mut commands,
         &characters,
         Vec3::new(0.0, -0.1, 100.0),
+        0.5,
         enemy_type,
     );
 
     commands
         .entity(sprite)
-        .insert(Enemy { enemy_type })
+        .insert(Enemy { enemy_type, level })
         .insert(stats)
-        .insert(Name::new("Bat"))
+        .insert(Name::new(format!("{:?} lv{}", enemy_type, level)))
         .add_child(health_text);
 }
 
@@ -0,0 +1,220 @@
+use bevy::prelude::*;
+
+use crate::{
+    ascii::spawn_ascii_text,
+    camera::CameraController,
+    input::{save_bindings, InputAction, InputActions, InputBindings, BINDING_SLOTS},
+    player::Player,
+    GameState, RESOLUTION, TILE_SIZE,
+};
+
+pub struct ControlsPlugin;
+
+#[derive(Component)]
+struct ControlsScreen;
+
+#[derive(Default)]
+struct ControlsMenu {
+    selected: usize,
+    //Which of the action's keys and buttons gets replaced
+    slot: usize,
+    rebinding: bool,
+    message: String,
+}
+
+//Every action plus the "reset to defaults" row
+const NUM_ROWS: usize = InputAction::ALL.len() + 1;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ControlsMenu>()
+            .add_system_set(SystemSet::on_update(GameState::Overworld).with_system(open_controls))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Controls).with_system(reset_controls_menu),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Controls)
+                    .with_system(controls_input)
+                    .with_system(draw_controls_screen.after(controls_input)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Controls).with_system(despawn_controls_screen),
+            );
+    }
+}
+
+fn open_controls(
+    player_query: Query<&Player>,
+    mut actions: ResMut<InputActions>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if player_query.single().active && actions.just_pressed(InputAction::OpenControls) {
+        //The screen would otherwise read the same press and close again
+        actions.clear();
+        let _ = state.push(GameState::Controls);
+    }
+}
+
+fn reset_controls_menu(mut menu: ResMut<ControlsMenu>) {
+    *menu = ControlsMenu::default();
+}
+
+fn controls_input(
+    mut menu: ResMut<ControlsMenu>,
+    mut bindings: ResMut<InputBindings>,
+    actions: Res<InputActions>,
+    keyboard: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if menu.rebinding {
+        let action = InputAction::ALL[menu.selected];
+        if keyboard.just_pressed(KeyCode::Escape) {
+            menu.rebinding = false;
+            menu.message = "Rebinding cancelled".to_string();
+        } else if let Some(key) = keyboard.get_just_pressed().next().copied() {
+            menu.rebinding = false;
+            menu.message = match bindings.rebind_key(action, menu.slot, key) {
+                Ok(()) => {
+                    save_bindings(&bindings);
+                    format!("{} bound to {:?}", action.name(), key)
+                }
+                Err(other) => format!("{:?} is used by {}", key, other.name()),
+            };
+        } else if let Some(button) = gamepad_buttons.get_just_pressed().next().copied() {
+            menu.rebinding = false;
+            menu.message = match bindings.rebind_button(action, menu.slot, button.1) {
+                Ok(()) => {
+                    save_bindings(&bindings);
+                    format!("{} bound to {:?}", action.name(), button.1)
+                }
+                Err(other) => format!("{:?} is used by {}", button.1, other.name()),
+            };
+        }
+        return;
+    }
+
+    if actions.any_just_pressed([InputAction::Cancel, InputAction::OpenControls]) {
+        let _ = state.pop();
+        return;
+    }
+
+    if actions.just_pressed(InputAction::MoveUp) {
+        menu.selected = (menu.selected + NUM_ROWS - 1) % NUM_ROWS;
+    }
+    if actions.just_pressed(InputAction::MoveDown) {
+        menu.selected = (menu.selected + 1) % NUM_ROWS;
+    }
+    if actions.just_pressed(InputAction::MenuLeft) {
+        menu.slot = (menu.slot + BINDING_SLOTS - 1) % BINDING_SLOTS;
+    }
+    if actions.just_pressed(InputAction::MenuRight) {
+        menu.slot = (menu.slot + 1) % BINDING_SLOTS;
+    }
+
+    if actions.just_pressed(InputAction::Confirm) {
+        if menu.selected == InputAction::ALL.len() {
+            *bindings = InputBindings::default();
+            save_bindings(&bindings);
+            menu.message = "Controls reset".to_string();
+        } else {
+            menu.rebinding = true;
+            menu.message = format!(
+                "Press a key for {} (Esc cancels)",
+                InputAction::ALL[menu.selected].name()
+            );
+        }
+    }
+}
+
+//Every slot is shown, empty ones as "-" and the selected one in brackets
+fn slots<T: std::fmt::Debug>(bindings: Option<&Vec<T>>, selected: Option<usize>) -> String {
+    (0..BINDING_SLOTS)
+        .map(|slot| {
+            let binding = bindings
+                .and_then(|bindings| bindings.get(slot))
+                .map(|binding| format!("{:?}", binding))
+                .unwrap_or_else(|| "-".to_string());
+            if selected == Some(slot) {
+                format!("[{}]", binding)
+            } else {
+                binding
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn binding_row(action: InputAction, bindings: &InputBindings, slot: Option<usize>) -> String {
+    let keys = slots(bindings.keys.get(&action), slot);
+    let buttons = slots(bindings.buttons.get(&action), slot);
+    format!("{:<12}{:<16}{}", action.name(), keys, buttons)
+}
+
+fn draw_controls_screen(
+    mut commands: Commands,
+    menu: Res<ControlsMenu>,
+    bindings: Res<InputBindings>,
+    screen_query: Query<Entity, With<ControlsScreen>>,
+    camera: Res<CameraController>,
+) {
+    if !menu.is_changed() && !bindings.is_changed() {
+        return;
+    }
+
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let left = -RESOLUTION + TILE_SIZE;
+    let mut lines = vec![String::from("Controls"), String::new()];
+    for (i, action) in InputAction::ALL.iter().enumerate() {
+        let (marker, slot) = if i == menu.selected {
+            ("> ", Some(menu.slot))
+        } else {
+            ("  ", None)
+        };
+        lines.push(format!(
+            "{}{}",
+            marker,
+            binding_row(*action, &bindings, slot)
+        ));
+    }
+    let marker = if menu.selected == InputAction::ALL.len() {
+        "> "
+    } else {
+        "  "
+    };
+    lines.push(format!("{}Reset to defaults", marker));
+    lines.push(String::new());
+    lines.push(menu.message.clone());
+
+    let mut texts = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        texts.push(spawn_ascii_text(
+            &mut commands,
+            line,
+            Vec3::new(left, 1.0 - (i as f32 + 1.5) * TILE_SIZE, 0.0),
+        ));
+    }
+
+    commands
+        .spawn()
+        .insert(ControlsScreen)
+        .insert(Name::new("Controls Screen"))
+        .insert(camera.screen_transform(Vec2::ZERO, 900.0))
+        .insert(GlobalTransform::default())
+        .push_children(&texts);
+}
+
+fn despawn_controls_screen(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<ControlsScreen>>,
+) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
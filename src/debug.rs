@@ -3,6 +3,7 @@ use bevy_inspector_egui::{RegisterInspectable, WorldInspectorPlugin};
 
 use crate::{
     combat::CombatStats,
+    editor::MapEditorPlugin,
     player::{EncounterTracker, Player},
 };
 
@@ -12,6 +13,7 @@ impl Plugin for DebugPlugin {
     fn build(&self, app: &mut App) {
         if cfg!(debug_assertions) {
             app.add_plugin(WorldInspectorPlugin::new())
+                .add_plugin(MapEditorPlugin)
                 .register_type::<EncounterTracker>()
                 .register_inspectable::<CombatStats>()
                 .register_inspectable::<Player>();
@@ -0,0 +1,375 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::map::{MapData, MapLayer, DEFAULT_SPAWN, EMPTY};
+
+const WALL: char = '#';
+const FLOOR: char = '.';
+//Floor of the encounter zone rooms
+const ZONE_FLOOR: char = ',';
+const CHEST: char = '=';
+const SPAWN: char = '$';
+const STAIRS_UP: char = '<';
+const STAIRS_DOWN: char = '>';
+
+//Generated floors are named dungeon/<seed>/<floor>, so warps and save data can point at them
+const NAME_PREFIX: &str = "dungeon/";
+//Next to the stairs down, where the player arrives when coming back up from the floor below
+pub const STAIRS_SPAWN: &str = "stairs";
+
+const NEIGHBOURS: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+pub fn map_name(seed: u64, floor: u32) -> String {
+    format!("{}{}/{}", NAME_PREFIX, seed, floor)
+}
+
+//The seed and floor of a generated map name, floors start at 1
+pub fn parse_map_name(name: &str) -> Option<(u64, u32)> {
+    let (seed, floor) = name.strip_prefix(NAME_PREFIX)?.split_once('/')?;
+    let floor = floor.parse().ok().filter(|floor| *floor > 0)?;
+    Some((seed.parse().ok()?, floor))
+}
+
+#[derive(Debug, Clone)]
+pub struct DungeonSettings {
+    pub width: usize,
+    pub height: usize,
+    pub max_rooms: usize,
+    //Smallest and largest room side, walls not included
+    pub room_size: (i32, i32),
+    //Chance of a room other than the first and the boss room being an encounter zone
+    pub zone_chance: f64,
+    //Fewest and most chests per floor
+    pub chests: (usize, usize),
+    //Items the chests are filled with
+    pub loot: Vec<String>,
+}
+
+impl Default for DungeonSettings {
+    fn default() -> Self {
+        DungeonSettings {
+            width: 48,
+            height: 32,
+            max_rooms: 10,
+            room_size: (4, 8),
+            zone_chance: 0.5,
+            chests: (1, 3),
+            loot: vec!["Potion".to_string(), "Ether".to_string()],
+        }
+    }
+}
+
+//The walkable inside of a room
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Room {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Room {
+    pub fn center(&self) -> (i32, i32) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    //Rooms keep at least one wall tile between them
+    fn overlaps(&self, other: &Room) -> bool {
+        self.x <= other.x + other.width
+            && other.x <= self.x + self.width
+            && self.y <= other.y + other.height
+            && other.y <= self.y + self.height
+    }
+
+    fn tiles(&self) -> impl Iterator<Item = (i32, i32)> {
+        let room = *self;
+        (room.y..room.y + room.height)
+            .flat_map(move |y| (room.x..room.x + room.width).map(move |x| (x, y)))
+    }
+}
+
+pub struct Dungeon {
+    pub data: MapData,
+    //The first room is where the player arrives
+    pub rooms: Vec<Room>,
+    pub boss_room: usize,
+    pub spawn: (i32, i32),
+    pub stairs_up: (i32, i32),
+    pub stairs_down: (i32, i32),
+    pub stairs_spawn: (i32, i32),
+    //Where the boss stands guarding the stairs down
+    pub boss: (i32, i32),
+    pub chests: Vec<((i32, i32), String)>,
+}
+
+impl Dungeon {
+    pub fn spawns(&self) -> [(&'static str, (i32, i32)); 2] {
+        [
+            (DEFAULT_SPAWN, self.spawn),
+            (STAIRS_SPAWN, self.stairs_spawn),
+        ]
+    }
+}
+
+//The same seed and floor always give the same dungeon.
+//Every room is reachable from the spawn, chests are only placed where they block nothing
+pub fn generate(seed: u64, floor: u32, settings: &DungeonSettings) -> Dungeon {
+    let mut rng = StdRng::seed_from_u64(seed ^ (floor as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    let (width, height) = (settings.width as i32, settings.height as i32);
+    let mut data = MapData::new(settings.width, settings.height);
+    for y in 0..height {
+        for x in 0..width {
+            data.set(MapLayer::Ground, x, y, WALL);
+        }
+    }
+
+    let mut rooms = place_rooms(&mut rng, settings);
+    //Chained left to right so the corridors don't cross the whole map back and forth
+    rooms.sort_by_key(|room| room.center());
+    for room in rooms.iter() {
+        for (x, y) in room.tiles() {
+            data.set(MapLayer::Ground, x, y, FLOOR);
+        }
+    }
+    for i in 1..rooms.len() {
+        dig_corridor(
+            &mut data,
+            &mut rng,
+            rooms[i - 1].center(),
+            rooms[i].center(),
+        );
+        //The odd loop keeps the layout from being a single line
+        if i >= 2 && rng.gen_bool(0.3) {
+            dig_corridor(
+                &mut data,
+                &mut rng,
+                rooms[i - 2].center(),
+                rooms[i].center(),
+            );
+        }
+    }
+
+    //The player starts at the leftmost room and the boss waits in the one furthest away from it
+    let start = rooms[0];
+    let stairs_up = (start.x, start.center().1);
+    let spawn = (start.x + 1, start.center().1);
+    let distances = walk_distances(&data, spawn);
+    let boss_room = (1..rooms.len())
+        .max_by_key(|i| distances.get(&rooms[*i].center()).copied().unwrap_or(0))
+        .expect("Dungeons have at least two rooms");
+    let boss_area = rooms[boss_room];
+    let boss = (boss_area.x, boss_area.center().1);
+    let stairs_down = (boss_area.x + boss_area.width - 1, boss_area.center().1);
+    let stairs_spawn = (stairs_down.0 - 1, stairs_down.1);
+
+    for (i, room) in rooms.iter().enumerate() {
+        if i != 0 && i != boss_room && rng.gen_bool(settings.zone_chance) {
+            for (x, y) in room.tiles() {
+                data.set(MapLayer::Ground, x, y, ZONE_FLOOR);
+            }
+        }
+    }
+    data.set(MapLayer::Objects, stairs_up.0, stairs_up.1, STAIRS_UP);
+    data.set(MapLayer::Objects, spawn.0, spawn.1, SPAWN);
+    data.set(MapLayer::Objects, stairs_down.0, stairs_down.1, STAIRS_DOWN);
+
+    let reserved = [spawn, stairs_up, stairs_down, stairs_spawn, boss];
+    let chests = place_chests(&mut data, &mut rng, settings, &rooms, &reserved);
+
+    Dungeon {
+        data,
+        rooms,
+        boss_room,
+        spawn,
+        stairs_up,
+        stairs_down,
+        stairs_spawn,
+        boss,
+        chests,
+    }
+}
+
+fn place_rooms(rng: &mut StdRng, settings: &DungeonSettings) -> Vec<Room> {
+    let (min, max) = settings.room_size;
+    let (width, height) = (settings.width as i32, settings.height as i32);
+    assert!(
+        min >= 4 && width >= max + 2 && height >= max + 2 && width >= 2 * min + 3,
+        "Dungeon of {}x{} is too small for two rooms of {} to {} tiles",
+        width,
+        height,
+        min,
+        max
+    );
+    assert!(
+        settings.max_rooms >= 2,
+        "Dungeons need at least two rooms, the boss and the way down aren't in the first"
+    );
+
+    let mut rooms: Vec<Room> = Vec::new();
+    for _ in 0..settings.max_rooms * 20 {
+        if rooms.len() >= settings.max_rooms {
+            break;
+        }
+        let room_width = rng.gen_range(min..=max);
+        let room_height = rng.gen_range(min..=max);
+        let room = Room {
+            x: rng.gen_range(1..=width - room_width - 1),
+            y: rng.gen_range(1..=height - room_height - 1),
+            width: room_width,
+            height: room_height,
+        };
+        if !rooms.iter().any(|other| room.overlaps(other)) {
+            rooms.push(room);
+        }
+    }
+    //Too crowded for a second room to fit at random, so two small ones go in opposite corners
+    if rooms.len() < 2 {
+        rooms = vec![
+            Room {
+                x: 1,
+                y: 1,
+                width: min,
+                height: min,
+            },
+            Room {
+                x: width - min - 1,
+                y: height - min - 1,
+                width: min,
+                height: min,
+            },
+        ];
+    }
+    rooms
+}
+
+//An L shaped corridor, bending either way
+fn dig_corridor(data: &mut MapData, rng: &mut StdRng, from: (i32, i32), to: (i32, i32)) {
+    let corner = if rng.gen_bool(0.5) {
+        (to.0, from.1)
+    } else {
+        (from.0, to.1)
+    };
+    for (a, b) in [(from, corner), (corner, to)] {
+        for x in a.0.min(b.0)..=a.0.max(b.0) {
+            for y in a.1.min(b.1)..=a.1.max(b.1) {
+                //Zone floors are laid after the corridors, rooms are already floor
+                if data.get(MapLayer::Ground, x, y) == WALL {
+                    data.set(MapLayer::Ground, x, y, FLOOR);
+                }
+            }
+        }
+    }
+}
+
+fn is_open(data: &MapData, tile: (i32, i32)) -> bool {
+    let ground = data.get(MapLayer::Ground, tile.0, tile.1);
+    ground != WALL && ground != EMPTY && data.get(MapLayer::Objects, tile.0, tile.1) != CHEST
+}
+
+//Steps from the start to every tile that can be walked to
+fn walk_distances(data: &MapData, start: (i32, i32)) -> HashMap<(i32, i32), u32> {
+    let mut distances = HashMap::new();
+    let mut queue = VecDeque::new();
+    distances.insert(start, 0);
+    queue.push_back(start);
+    while let Some(tile) = queue.pop_front() {
+        let steps = distances[&tile];
+        for (dx, dy) in NEIGHBOURS {
+            let next = (tile.0 + dx, tile.1 + dy);
+            if is_open(data, next) && !distances.contains_key(&next) {
+                distances.insert(next, steps + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+    distances
+}
+
+fn place_chests(
+    data: &mut MapData,
+    rng: &mut StdRng,
+    settings: &DungeonSettings,
+    rooms: &[Room],
+    reserved: &[(i32, i32)],
+) -> Vec<((i32, i32), String)> {
+    let mut chests = Vec::new();
+    if rooms.len() < 2 || settings.loot.is_empty() {
+        return chests;
+    }
+    let count = rng.gen_range(settings.chests.0..=settings.chests.1.max(settings.chests.0));
+    let reachable = walk_distances(data, reserved[0]).len();
+    let mut taken: HashSet<(i32, i32)> = reserved.iter().copied().collect();
+
+    for _ in 0..count * 10 {
+        if chests.len() >= count {
+            break;
+        }
+        let room = rooms[rng.gen_range(1..rooms.len())];
+        let tile = (
+            rng.gen_range(room.x..room.x + room.width),
+            rng.gen_range(room.y..room.y + room.height),
+        );
+        if !taken.insert(tile) {
+            continue;
+        }
+        //A chest is solid, so it may not cut off any part of the floor
+        data.set(MapLayer::Objects, tile.0, tile.1, CHEST);
+        if walk_distances(data, reserved[0]).len() != reachable - 1 {
+            data.set(MapLayer::Objects, tile.0, tile.1, EMPTY);
+            continue;
+        }
+        let item = settings.loot.choose(rng).cloned().unwrap_or_default();
+        chests.push((tile, item));
+    }
+    chests
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_floor() {
+        let settings = DungeonSettings::default();
+        let a = generate(42, 3, &settings);
+        let b = generate(42, 3, &settings);
+        assert_eq!(a.data.to_text(), b.data.to_text());
+        assert_eq!(a.rooms, b.rooms);
+        assert_eq!(a.chests, b.chests);
+        assert_eq!((a.boss, a.stairs_down), (b.boss, b.stairs_down));
+        assert_ne!(a.data.to_text(), generate(42, 4, &settings).data.to_text());
+    }
+
+    #[test]
+    fn every_room_is_reachable_from_the_stairs() {
+        let settings = DungeonSettings::default();
+        for seed in 0..20 {
+            let dungeon = generate(seed, 1, &settings);
+            let reachable = walk_distances(&dungeon.data, dungeon.stairs_up);
+            let chests: HashSet<_> = dungeon.chests.iter().map(|(tile, _)| *tile).collect();
+            for room in dungeon.rooms.iter() {
+                for tile in room.tiles().filter(|tile| !chests.contains(tile)) {
+                    assert!(reachable.contains_key(&tile), "seed {} {:?}", seed, tile);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn boss_is_never_on_the_stairs_up() {
+        //Only just wide enough for two rooms
+        let settings = DungeonSettings {
+            width: 11,
+            height: 6,
+            room_size: (4, 4),
+            ..Default::default()
+        };
+        for seed in 0..20 {
+            let dungeon = generate(seed, 1, &settings);
+            assert!(dungeon.rooms.len() >= 2);
+            assert_ne!(dungeon.boss_room, 0);
+            assert_ne!(dungeon.boss, dungeon.stairs_up);
+        }
+    }
+}
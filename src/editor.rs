@@ -0,0 +1,261 @@
+use bevy::prelude::*;
+use bevy_2d_rpg::{
+    collision::CollisionMap,
+    map::{LegendEntry, MapLayer, EMPTY},
+};
+use bevy_inspector_egui::bevy_egui::{egui, EguiContext};
+
+use crate::{
+    camera::CameraController,
+    player::Player,
+    tilemap::{save_map, sync_tile, world_to_tile, CurrentMap, MapChunks},
+    GameState, TILE_SIZE,
+};
+
+pub struct MapEditorPlugin;
+
+//Tiles per second the view pans at
+const PAN_SPEED: f32 = 12.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EditorTool {
+    Paint,
+    Spawn,
+}
+
+//F3 switches between playing and editing the current map, only added in debug builds
+struct MapEditor {
+    active: bool,
+    tool: EditorTool,
+    glyph: char,
+    layer: MapLayer,
+    //Named spawn placed by the spawn tool
+    spawn_name: String,
+    status: String,
+}
+
+impl Default for MapEditor {
+    fn default() -> Self {
+        MapEditor {
+            active: false,
+            tool: EditorTool::Paint,
+            glyph: '.',
+            layer: MapLayer::Ground,
+            spawn_name: String::new(),
+            status: String::new(),
+        }
+    }
+}
+
+impl Plugin for MapEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapEditor>().add_system_set(
+            SystemSet::on_update(GameState::Overworld)
+                .with_system(toggle_editor)
+                .with_system(pan_editor_view.after(toggle_editor))
+                .with_system(editor_panel.after(toggle_editor))
+                .with_system(paint_tiles.after(editor_panel)),
+        );
+    }
+}
+
+fn toggle_editor(
+    keyboard: Res<Input<KeyCode>>,
+    mut editor: ResMut<MapEditor>,
+    mut player_query: Query<&mut Player>,
+    mut camera: ResMut<CameraController>,
+) {
+    if !keyboard.just_pressed(KeyCode::F3) {
+        return;
+    }
+    let mut player = player_query.single_mut();
+    //Not while a textbox or warp has the player busy
+    if !editor.active && !player.active {
+        return;
+    }
+    editor.active = !editor.active;
+    player.active = !editor.active;
+    let position = camera.position;
+    camera.free_look = editor.active.then_some(position);
+    editor.status.clear();
+}
+
+fn pan_editor_view(
+    keyboard: Res<Input<KeyCode>>,
+    editor: Res<MapEditor>,
+    mut camera: ResMut<CameraController>,
+    collision_map: Res<CollisionMap>,
+    time: Res<Time>,
+) {
+    if !editor.active {
+        return;
+    }
+    let mut direction = Vec2::ZERO;
+    for (key, step) in [
+        (KeyCode::Left, -Vec2::X),
+        (KeyCode::Right, Vec2::X),
+        (KeyCode::Up, Vec2::Y),
+        (KeyCode::Down, -Vec2::Y),
+    ] {
+        if keyboard.pressed(key) {
+            direction += step;
+        }
+    }
+    let speed = PAN_SPEED * TILE_SIZE * camera.zoom() * time.delta_seconds();
+    let map_max = Vec2::new(
+        collision_map.width() as f32,
+        -(collision_map.height() as f32),
+    ) * TILE_SIZE;
+    if let Some(position) = camera.free_look.as_mut() {
+        *position = (*position + direction * speed).clamp(map_max * Vec2::Y, map_max * Vec2::X);
+    }
+}
+
+//Where a glyph goes when it is picked from the palette
+fn palette_layer(entry: &LegendEntry, current: MapLayer) -> MapLayer {
+    match (entry.object, current) {
+        (Some(_), _) => MapLayer::Objects,
+        (None, MapLayer::Objects) => MapLayer::Ground,
+        (None, layer) => layer,
+    }
+}
+
+fn editor_panel(
+    mut egui_context: ResMut<EguiContext>,
+    mut editor: ResMut<MapEditor>,
+    current_map: Res<CurrentMap>,
+) {
+    if !editor.active {
+        return;
+    }
+    let editor = &mut *editor;
+    let mut palette: Vec<(&char, &LegendEntry)> = current_map.legend.0.iter().collect();
+    palette.sort_by_key(|(_, entry)| entry.name.as_str());
+
+    egui::Window::new("Map editor").show(egui_context.ctx_mut(), |ui| {
+        ui.label(format!("Editing {}", current_map.name));
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut editor.tool, EditorTool::Paint, "Paint");
+            ui.selectable_value(&mut editor.tool, EditorTool::Spawn, "Spawn point");
+        });
+        ui.separator();
+
+        match editor.tool {
+            EditorTool::Paint => {
+                ui.horizontal(|ui| {
+                    for layer in MapLayer::ALL {
+                        ui.selectable_value(&mut editor.layer, layer, layer.name());
+                    }
+                });
+                let groups: [(&str, fn(&LegendEntry) -> bool); 3] = [
+                    ("Tiles", |entry| {
+                        entry.zone.is_none() && entry.object.is_none()
+                    }),
+                    ("Encounter zones", |entry| entry.zone.is_some()),
+                    ("Objects and NPCs", |entry| entry.object.is_some()),
+                ];
+                for (title, in_group) in groups {
+                    ui.label(title);
+                    ui.horizontal_wrapped(|ui| {
+                        for (glyph, entry) in palette.iter().filter(|(_, entry)| in_group(entry)) {
+                            let label = format!("{} {}", glyph, entry.name);
+                            if ui
+                                .selectable_label(editor.glyph == **glyph, label)
+                                .clicked()
+                            {
+                                editor.glyph = **glyph;
+                                editor.layer = palette_layer(entry, editor.layer);
+                            }
+                        }
+                    });
+                }
+                ui.label("Left click paints, right click erases");
+            }
+            EditorTool::Spawn => {
+                ui.horizontal(|ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut editor.spawn_name);
+                });
+                let mut spawns: Vec<_> = current_map.spawns.iter().collect();
+                spawns.sort();
+                for (name, tile) in spawns {
+                    ui.label(format!("{} at {:?}", name, tile));
+                }
+                ui.label("Left click places the named spawn");
+            }
+        }
+
+        ui.separator();
+        ui.label("Arrow keys pan, F3 goes back to playing");
+        if ui.button("Save").clicked() {
+            editor.status = match save_map(&current_map) {
+                Ok(()) => "Saved, NPCs and objects show once the map reloads".to_string(),
+                Err(err) => err,
+            };
+        }
+        if !editor.status.is_empty() {
+            ui.label(editor.status.as_str());
+        }
+    });
+}
+
+//Edits go into the map data right away, so painted tiles and walls work before saving
+fn paint_tiles(
+    windows: Res<Windows>,
+    mouse: Res<Input<MouseButton>>,
+    mut egui_context: ResMut<EguiContext>,
+    mut editor: ResMut<MapEditor>,
+    camera: Res<CameraController>,
+    mut current_map: ResMut<CurrentMap>,
+    mut chunks: ResMut<MapChunks>,
+    mut collision_map: ResMut<CollisionMap>,
+) {
+    if !editor.active {
+        return;
+    }
+    let ctx = egui_context.ctx_mut();
+    if ctx.wants_pointer_input() || ctx.is_pointer_over_area() {
+        return;
+    }
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let cursor = match window.cursor_position() {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    let screen = cursor / Vec2::new(window.width(), window.height()) * 2.0 - Vec2::ONE;
+    let tile = world_to_tile(camera.screen_to_world(screen).extend(0.0));
+    let (width, height) = (current_map.data.width(), current_map.data.height());
+    if tile.0 < 0 || tile.1 < 0 || tile.0 >= width as i32 || tile.1 >= height as i32 {
+        return;
+    }
+
+    match editor.tool {
+        EditorTool::Paint => {
+            let glyph = if mouse.pressed(MouseButton::Left) {
+                editor.glyph
+            } else if mouse.pressed(MouseButton::Right) {
+                EMPTY
+            } else {
+                return;
+            };
+            let layer = editor.layer;
+            if current_map.source.get(layer, tile.0, tile.1) == glyph {
+                return;
+            }
+            current_map.source.set(layer, tile.0, tile.1, glyph);
+            current_map.data.set(layer, tile.0, tile.1, glyph);
+            sync_tile(&mut chunks, &mut collision_map, &current_map, tile);
+        }
+        EditorTool::Spawn => {
+            let name = editor.spawn_name.trim().to_string();
+            if !mouse.just_pressed(MouseButton::Left) || name.is_empty() {
+                return;
+            }
+            editor.status = format!("Spawn {} placed at {:?}", name, tile);
+            current_map.spawns.insert(name, tile);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+use serde::Deserialize;
+
+use crate::{clock::TimeOfDay, combat::EnemyType, map_asset::RonAsset};
+
+pub struct EncounterPlugin;
+
+#[derive(Deserialize)]
+pub struct EncounterZone {
+    //Referred to by the map legend
+    pub name: String,
+    //Enemy types with their relative weights
+    pub enemies: Vec<(EnemyType, u32)>,
+    //Enemies listed here only show up at those times of day
+    #[serde(default)]
+    pub active_times: HashMap<EnemyType, Vec<TimeOfDay>>,
+    //Multiplier on the encounter chance, 2.0 is twice as often
+    pub rate: f32,
+    pub levels: (usize, usize),
+    pub music: String,
+}
+
+//Empty until encounter_zones.ron has loaded
+#[derive(Default)]
+pub struct EncounterZones(pub Vec<EncounterZone>);
+
+const ZONES_PATH: &str = "encounter_zones.ron";
+
+//Watched so the map is rebuilt with the new zones when the file is saved
+pub struct ZonesFile(pub Handle<RonAsset>);
+
+//The chance of a fight on each tile walked grows with the distance since the last one
+pub struct EncounterSettings {
+    //Tiles that are always safe after the meter resets
+    pub safe_steps: f32,
+    //Tiles over which the chance climbs from base_chance to max_chance
+    pub ramp_steps: f32,
+    pub base_chance: f32,
+    pub max_chance: f32,
+    //Tiles walked in zones without any fight after a battle or a flee
+    pub grace_steps: f32,
+}
+
+impl Default for EncounterSettings {
+    fn default() -> Self {
+        EncounterSettings {
+            safe_steps: 4.0,
+            ramp_steps: 20.0,
+            base_chance: 0.03,
+            max_chance: 0.25,
+            grace_steps: 8.0,
+        }
+    }
+}
+
+impl EncounterSettings {
+    pub fn danger(&self, distance: f32) -> f32 {
+        if distance < self.safe_steps {
+            return 0.0;
+        }
+        let ramp = ((distance - self.safe_steps) / self.ramp_steps.max(1.0)).min(1.0);
+        self.base_chance + (self.max_chance - self.base_chance) * ramp
+    }
+}
+
+//The fight was meant to carry its zone as GameState::Combat(zone), but bevy 0.7 run criteria
+//like on_enter compare the whole state value, so each zone would need its own system sets.
+//The enemy that started the fight is stored here instead, next to a plain GameState::Combat
+pub struct ActiveEncounter {
+    //Roaming monsters can be met outside of any zone
+    pub zone: Option<usize>,
+    pub enemy: EnemyType,
+    pub level: usize,
+}
+
+impl Plugin for EncounterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EncounterZones>()
+            .init_resource::<EncounterSettings>()
+            .add_startup_system(load_zones)
+            .add_system(update_zones);
+    }
+}
+
+impl EncounterZone {
+    //None when nothing in the zone is out at this time of day
+    pub fn pick_enemy(&self, time: TimeOfDay) -> Option<EnemyType> {
+        let available: Vec<(EnemyType, u32)> = self
+            .enemies
+            .iter()
+            .copied()
+            .filter(|(enemy_type, _)| {
+                self.active_times
+                    .get(enemy_type)
+                    .is_none_or(|times| times.contains(&time))
+            })
+            .collect();
+        let total: u32 = available.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = thread_rng().gen_range(0..total);
+        for (enemy_type, weight) in available.iter() {
+            if roll < *weight {
+                return Some(*enemy_type);
+            }
+            roll -= weight;
+        }
+        None
+    }
+
+    pub fn pick_level(&self) -> usize {
+        thread_rng().gen_range(self.levels.0..=self.levels.1.max(self.levels.0))
+    }
+}
+
+impl EncounterZones {
+    pub fn zone_by_name(&self, name: &str) -> Option<usize> {
+        self.0.iter().position(|zone| zone.name == name)
+    }
+
+    pub fn get(&self, zone: usize) -> Option<&EncounterZone> {
+        self.0.get(zone)
+    }
+}
+
+//Read through the RonAsset loader the TileMapPlugin registers
+fn load_zones(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(ZonesFile(assets.load(ZONES_PATH)));
+}
+
+//A malformed file keeps the last good zones
+fn update_zones(
+    mut events: EventReader<AssetEvent<RonAsset>>,
+    file: Res<ZonesFile>,
+    files: Res<Assets<RonAsset>>,
+    mut zones: ResMut<EncounterZones>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if *handle != file.0 {
+            continue;
+        }
+        match files.get(handle).map(|file| file.parse()) {
+            Some(Ok(loaded)) => zones.0 = loaded,
+            Some(Err(err)) => error!("Encounter zones {} are malformed: {}", ZONES_PATH, err),
+            None => {}
+        }
+    }
+}
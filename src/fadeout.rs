@@ -1,6 +1,10 @@
 use bevy::{prelude::*, reflect::erased_serde::__private::serde::__private::de};
 
-use crate::{ascii::AsciiSheet, GameState};
+use crate::{
+    ascii::AsciiSheet,
+    tilemap::{Warp, WarpEvent},
+    GameState,
+};
 
 pub struct FadeoutPlugin;
 
@@ -9,6 +13,8 @@ pub struct ScreenFade {
     alpha: f32,
     sent: bool,
     next_state: Option<GameState>,
+    //Warps swap the map while the screen is dark instead of changing state
+    warp: Option<Warp>,
     timer: Timer,
 }
 
@@ -39,6 +45,7 @@ fn fadeout(
     mut commands: Commands,
     mut fade_query: Query<(Entity, &mut ScreenFade, &mut TextureAtlasSprite)>,
     mut state: ResMut<State<GameState>>,
+    mut warp_events: EventWriter<WarpEvent>,
     time: Res<Time>,
 ) {
     for (entity, mut fade, mut sprite) in fade_query.iter_mut() {
@@ -51,7 +58,9 @@ fn fadeout(
         sprite.color.set_a(fade.alpha);
 
         if fade.timer.percent() > 0.5 && !fade.sent {
-            if let Some(next_state) = fade.next_state {
+            if let Some(warp) = fade.warp.take() {
+                warp_events.send(WarpEvent(warp));
+            } else if let Some(next_state) = fade.next_state {
                 state.push(next_state);
             } else {
                 state.pop().unwrap();
@@ -69,6 +78,19 @@ pub fn create_fadeout(
     commands: &mut Commands,
     next_state: Option<GameState>,
     ascii: &Res<AsciiSheet>,
+) {
+    spawn_fadeout(commands, next_state, None, ascii);
+}
+
+pub fn create_warp_fadeout(commands: &mut Commands, warp: Warp, ascii: &Res<AsciiSheet>) {
+    spawn_fadeout(commands, None, Some(warp), ascii);
+}
+
+fn spawn_fadeout(
+    commands: &mut Commands,
+    next_state: Option<GameState>,
+    warp: Option<Warp>,
+    ascii: &Res<AsciiSheet>,
 ) {
     let mut sprite = TextureAtlasSprite::new(0);
     sprite.color = Color::rgba(0.1, 0.1, 0.15, 0.0);
@@ -88,6 +110,7 @@ pub fn create_fadeout(
             alpha: 0.0,
             sent: false,
             next_state: next_state,
+            warp: warp,
             timer: Timer::from_seconds(1.0, false),
         })
         .insert(Name::new("Fadeout"));
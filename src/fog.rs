@@ -0,0 +1,172 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bevy_2d_rpg::{
+    map::{MapLayer, ObjectType},
+    quads::{chunk_of, chunk_tiles, map_chunks, Quad},
+    sight::visible_tiles,
+};
+
+use crate::{
+    ascii::AsciiSheet,
+    batch::MeshBatcher,
+    player::Player,
+    save::ExploredMaps,
+    tilemap::{tile_to_world, world_to_tile, CurrentMap, Map},
+    GameState, TILE_SIZE,
+};
+
+pub struct FogPlugin;
+
+//Tiles the player sees in each direction
+const SIGHT_RADIUS: i32 = 6;
+//Over NPCs and roamers but under the player
+const FOG_Z: f32 = 850.0;
+const UNEXPLORED: Color = Color::BLACK;
+//Explored tiles that are out of sight
+const REMEMBERED: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+//The first glyph of the ascii sheet is a solid block, tinted to the fog colour
+const FOG_INDEX: usize = 0;
+
+//What the player sees right now, and the fog meshes covering the rest of a dark map
+#[derive(Default)]
+pub struct Sight {
+    map: Option<Entity>,
+    center: Option<(i32, i32)>,
+    visible: HashSet<(i32, i32)>,
+    meshes: HashMap<(i32, i32), Vec<Entity>>,
+    //Remeshed at the end of the frame
+    dirty: HashSet<(i32, i32)>,
+}
+
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Sight>()
+            .add_system_set(SystemSet::on_update(GameState::Overworld).with_system(update_sight))
+            .add_system_to_stage(CoreStage::PostUpdate, mesh_fog);
+    }
+}
+
+//Walls and closed doors block sight, NPCs don't since they walk off the tile they start on
+fn blocks_sight(current_map: &CurrentMap, tile: (i32, i32)) -> bool {
+    let (data, legend) = (&current_map.data, &current_map.legend);
+    let npc = legend
+        .get(data.get(MapLayer::Objects, tile.0, tile.1))
+        .is_some_and(|entry| entry.object == Some(ObjectType::Npc));
+    !npc && data.is_solid(legend, tile.0, tile.1)
+}
+
+fn update_sight(
+    player_query: Query<&Transform, With<Player>>,
+    map_query: Query<Entity, With<Map>>,
+    current_map: Res<CurrentMap>,
+    mut sight: ResMut<Sight>,
+    mut explored: ResMut<ExploredMaps>,
+) {
+    let map = match map_query.get_single() {
+        Ok(map) => map,
+        Err(_) => return,
+    };
+    //The old fog meshes went with the old map
+    if sight.map != Some(map) {
+        sight.map = Some(map);
+        sight.center = None;
+        sight.visible.clear();
+        sight.meshes.clear();
+        sight.dirty.clear();
+        if current_map.dark {
+            let data = &current_map.data;
+            sight.dirty.extend(map_chunks(data.width(), data.height()));
+        }
+    }
+
+    //Opened doors and edited walls change what is seen from the same tile
+    let center = world_to_tile(player_query.single().translation);
+    if sight.center == Some(center) && !current_map.is_changed() {
+        return;
+    }
+    sight.center = Some(center);
+    let (width, height) = (current_map.data.width(), current_map.data.height());
+    let in_map = |tile: &(i32, i32)| {
+        (0..width as i32).contains(&tile.0) && (0..height as i32).contains(&tile.1)
+    };
+    let visible: HashSet<(i32, i32)> = visible_tiles(center, SIGHT_RADIUS, |tile| {
+        blocks_sight(&current_map, tile)
+    })
+    .into_iter()
+    .filter(in_map)
+    .collect();
+    if current_map.dark {
+        let changed: Vec<_> = sight
+            .visible
+            .symmetric_difference(&visible)
+            .map(|tile| chunk_of(*tile))
+            .collect();
+        sight.dirty.extend(changed);
+    }
+
+    //Left untouched when nothing new was seen, so it doesn't count as changed
+    let seen_before = explored
+        .maps
+        .get(&current_map.name)
+        .is_some_and(|tiles| visible.is_subset(tiles));
+    if !seen_before {
+        explored
+            .maps
+            .entry(current_map.name.clone())
+            .or_default()
+            .extend(visible.iter().copied());
+    }
+    sight.visible = visible;
+}
+
+fn mesh_fog(
+    mut batcher: MeshBatcher,
+    mut sight: ResMut<Sight>,
+    map_query: Query<Entity, With<Map>>,
+    current_map: Res<CurrentMap>,
+    explored: Res<ExploredMaps>,
+    ascii: Res<AsciiSheet>,
+) {
+    //Waits for update_sight to catch up after the map was swapped
+    let map = match map_query.get_single() {
+        Ok(map) if sight.map == Some(map) && !sight.dirty.is_empty() => map,
+        _ => return,
+    };
+    let data = &current_map.data;
+    let explored = explored.maps.get(&current_map.name);
+    let dirty = std::mem::take(&mut sight.dirty);
+    for chunk in dirty {
+        let mut unexplored = Vec::new();
+        let mut remembered = Vec::new();
+        for tile in chunk_tiles(chunk, data.width(), data.height()) {
+            if sight.visible.contains(&tile) {
+                continue;
+            }
+            let quads = if explored.is_some_and(|tiles| tiles.contains(&tile)) {
+                &mut remembered
+            } else {
+                &mut unexplored
+            };
+            quads.push(Quad {
+                center: tile_to_world(tile, 0.0),
+                size: Vec2::splat(TILE_SIZE),
+                index: FOG_INDEX,
+            });
+        }
+
+        //Recursive so the map's Children forgets them as well
+        for old in sight.meshes.remove(&chunk).unwrap_or_default() {
+            batcher.commands.entity(old).despawn_recursive();
+        }
+        let mut meshes = Vec::new();
+        for (color, quads) in [(UNEXPLORED, unexplored), (REMEMBERED, remembered)] {
+            if !quads.is_empty() {
+                let transform = Transform::from_xyz(0.0, 0.0, FOG_Z);
+                meshes.push(batcher.spawn_batch(&ascii.0, color, &quads, transform));
+            }
+        }
+        batcher.commands.entity(map).push_children(&meshes);
+        sight.meshes.insert(chunk, meshes);
+    }
+}
@@ -1,4 +1,4 @@
-use std::default;
+use std::collections::HashMap;
 
 use bevy::prelude::*;
 
@@ -14,8 +14,49 @@ pub struct CharacterSheet {
     pub player_down: [usize; 3],
     pub player_left: [usize; 3],
     pub player_right: [usize; 3],
+    pub npc_up: [usize; 3],
+    pub npc_down: [usize; 3],
+    pub npc_left: [usize; 3],
+    pub npc_right: [usize; 3],
 }
 
+pub const BASIC_TILES: &str = "graphics/tiles/basictiles.png";
+pub const THINGS: &str = "graphics/objects/things.png";
+
+//Graphical tile atlases by image path under assets/, as named in the legend.
+//The shipped sheets are loaded up front, tilesets of Tiled maps are added as they load
+#[derive(Default)]
+pub struct TileSheet {
+    pub atlases: HashMap<String, Handle<TextureAtlas>>,
+    pub style: TileStyle,
+}
+
+//How map tiles are drawn, switched at runtime with ToggleTiles
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TileStyle {
+    Ascii,
+    #[default]
+    Graphics,
+}
+
+impl TileSheet {
+    //None when ascii is wanted or the image isn't loaded, the legend's glyph is drawn then
+    pub fn get(&self, image: &str) -> Option<&Handle<TextureAtlas>> {
+        match self.style {
+            TileStyle::Ascii => None,
+            TileStyle::Graphics => self.atlases.get(image),
+        }
+    }
+
+    pub fn toggle_style(&mut self) {
+        self.style = match self.style {
+            TileStyle::Ascii => TileStyle::Graphics,
+            TileStyle::Graphics => TileStyle::Ascii,
+        };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum FacingDirection {
     Up,
     Down,
@@ -28,6 +69,15 @@ pub struct PlayerGraphics {
     pub facing: FacingDirection,
 }
 
+#[derive(Component)]
+pub struct NpcGraphics {
+    pub facing: FacingDirection,
+}
+
+//Seconds every animated map tile is played against, so all tiles of a kind stay in step
+#[derive(Default)]
+pub struct TileClock(pub f32);
+
 #[derive(Component)]
 pub struct FrameAnimation {
     pub timer: Timer,
@@ -38,8 +88,11 @@ pub struct FrameAnimation {
 impl Plugin for GraphicsPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system_to_stage(StartupStage::PreStartup, Self::load_graphics)
+            .init_resource::<TileClock>()
             .add_system(Self::frame_animation)
-            .add_system(Self::update_player_graphics);
+            .add_system(Self::tick_tile_clock)
+            .add_system(Self::update_player_graphics)
+            .add_system(Self::update_npc_graphics);
     }
 }
 
@@ -47,13 +100,14 @@ pub fn spawn_enemy_sprite(
     commands: &mut Commands,
     characters: &CharacterSheet,
     translation: Vec3,
+    size: f32,
     enemy_type: EnemyType,
 ) -> Entity {
     let mut sprite = match enemy_type {
         EnemyType::Bat => TextureAtlasSprite::new(characters.bat_frames[0]),
         EnemyType::Ghost => TextureAtlasSprite::new(characters.ghost_frames[0]),
     };
-    sprite.custom_size = Some(Vec2::splat(0.5));
+    sprite.custom_size = Some(Vec2::splat(size));
     let animation = match enemy_type {
         EnemyType::Bat => FrameAnimation {
             timer: Timer::from_seconds(0.2, true),
@@ -99,6 +153,22 @@ impl GraphicsPlugin {
         let columns = 12;
 
         let atlas_handle = texture_atlases.add(atlas);
+
+        let mut tiles = TileSheet::default();
+        for (path, columns, rows) in [(BASIC_TILES, 8, 15), (THINGS, 12, 8)] {
+            let atlas = TextureAtlas::from_grid_with_padding(
+                assets.load(path),
+                Vec2::splat(16.0),
+                columns,
+                rows,
+                Vec2::splat(0.01),
+            );
+            tiles
+                .atlases
+                .insert(path.to_string(), texture_atlases.add(atlas));
+        }
+        commands.insert_resource(tiles);
+
         commands.insert_resource(CharacterSheet {
             handle: atlas_handle,
             player_down: [3, 4, 5],
@@ -107,6 +177,10 @@ impl GraphicsPlugin {
             player_up: [columns * 3 + 3, columns * 3 + 4, columns * 3 + 5],
             bat_frames: [columns * 4 + 3, columns * 4 + 4, columns * 4 + 5],
             ghost_frames: [columns * 4 + 6, columns * 4 + 7, columns * 4 + 8],
+            npc_down: [0, 1, 2],
+            npc_left: [columns, columns + 1, columns + 2],
+            npc_right: [columns * 2, columns * 2 + 1, columns * 2 + 2],
+            npc_up: [columns * 3, columns * 3 + 1, columns * 3 + 2],
         });
     }
 
@@ -124,6 +198,24 @@ impl GraphicsPlugin {
         }
     }
 
+    fn update_npc_graphics(
+        mut sprites_query: Query<(&NpcGraphics, &mut FrameAnimation), Changed<NpcGraphics>>,
+        characters: Res<CharacterSheet>,
+    ) {
+        for (graphics, mut animation) in sprites_query.iter_mut() {
+            animation.frames = match graphics.facing {
+                FacingDirection::Up => characters.npc_up.to_vec(),
+                FacingDirection::Down => characters.npc_down.to_vec(),
+                FacingDirection::Left => characters.npc_left.to_vec(),
+                FacingDirection::Right => characters.npc_right.to_vec(),
+            }
+        }
+    }
+
+    fn tick_tile_clock(mut clock: ResMut<TileClock>, time: Res<Time>) {
+        clock.0 += time.delta_seconds();
+    }
+
     fn frame_animation(
         mut sprites_query: Query<(&mut TextureAtlasSprite, &mut FrameAnimation)>,
         time: Res<Time>,
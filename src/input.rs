@@ -0,0 +1,451 @@
+use std::{collections::BTreeMap, fs};
+
+use bevy::{input::InputSystem, prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+
+pub struct GameInputPlugin;
+
+//How far a stick has to be pushed before it counts as a press
+const STICK_THRESHOLD: f32 = 0.5;
+const CONTROLS_PATH: &str = "controls.ron";
+//Keys and buttons an action can be given in the controls screen, Cancel uses both by default
+pub const BINDING_SLOTS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum InputAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Confirm,
+    Cancel,
+    MenuLeft,
+    MenuRight,
+    VolumeUp,
+    VolumeDown,
+    OpenControls,
+    ZoomIn,
+    ZoomOut,
+    ToggleTiles,
+    OpenMap,
+}
+
+//Actions only clash with each other when they can be read at the same time
+#[derive(PartialEq, Eq)]
+enum ActionContext {
+    Field,
+    Menu,
+    Shared,
+    Global,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 15] = [
+        InputAction::MoveUp,
+        InputAction::MoveDown,
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::Confirm,
+        InputAction::Cancel,
+        InputAction::MenuLeft,
+        InputAction::MenuRight,
+        InputAction::VolumeUp,
+        InputAction::VolumeDown,
+        InputAction::OpenControls,
+        InputAction::ZoomIn,
+        InputAction::ZoomOut,
+        InputAction::ToggleTiles,
+        InputAction::OpenMap,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputAction::MoveUp => "Move up",
+            InputAction::MoveDown => "Move down",
+            InputAction::MoveLeft => "Move left",
+            InputAction::MoveRight => "Move right",
+            InputAction::Confirm => "Confirm",
+            InputAction::Cancel => "Cancel",
+            InputAction::MenuLeft => "Menu left",
+            InputAction::MenuRight => "Menu right",
+            InputAction::VolumeUp => "Volume up",
+            InputAction::VolumeDown => "Volume down",
+            InputAction::OpenControls => "Controls",
+            InputAction::ZoomIn => "Zoom in",
+            InputAction::ZoomOut => "Zoom out",
+            InputAction::ToggleTiles => "Toggle tiles",
+            InputAction::OpenMap => "Map",
+        }
+    }
+
+    fn context(&self) -> ActionContext {
+        match self {
+            InputAction::MoveUp
+            | InputAction::MoveDown
+            | InputAction::MoveLeft
+            | InputAction::MoveRight
+            | InputAction::ZoomIn
+            | InputAction::ZoomOut
+            | InputAction::ToggleTiles => ActionContext::Field,
+            InputAction::MenuLeft | InputAction::MenuRight => ActionContext::Menu,
+            InputAction::Confirm | InputAction::Cancel => ActionContext::Shared,
+            InputAction::VolumeUp
+            | InputAction::VolumeDown
+            | InputAction::OpenControls
+            | InputAction::OpenMap => ActionContext::Global,
+        }
+    }
+
+    pub fn conflicts_with(&self, other: InputAction) -> bool {
+        if *self == other {
+            return false;
+        }
+        match (self.context(), other.context()) {
+            (ActionContext::Global, _) | (_, ActionContext::Global) => true,
+            (ActionContext::Shared, _) | (_, ActionContext::Shared) => true,
+            (a, b) => a == b,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub keys: BTreeMap<InputAction, Vec<KeyCode>>,
+    pub buttons: BTreeMap<InputAction, Vec<GamepadButtonType>>,
+}
+
+impl InputBindings {
+    //Replaces the key in one slot of an action, the others are kept.
+    //Returns the action already using the key on a clash
+    pub fn rebind_key(
+        &mut self,
+        action: InputAction,
+        slot: usize,
+        key: KeyCode,
+    ) -> Result<(), InputAction> {
+        if let Some((other, _)) = self
+            .keys
+            .iter()
+            .find(|(other, keys)| action.conflicts_with(**other) && keys.contains(&key))
+        {
+            return Err(*other);
+        }
+        set_slot(self.keys.entry(action).or_default(), slot, key);
+        Ok(())
+    }
+
+    pub fn rebind_button(
+        &mut self,
+        action: InputAction,
+        slot: usize,
+        button: GamepadButtonType,
+    ) -> Result<(), InputAction> {
+        if let Some((other, _)) = self
+            .buttons
+            .iter()
+            .find(|(other, buttons)| action.conflicts_with(**other) && buttons.contains(&button))
+        {
+            return Err(*other);
+        }
+        set_slot(self.buttons.entry(action).or_default(), slot, button);
+        Ok(())
+    }
+
+    pub fn conflicts(&self) -> Vec<(InputAction, InputAction)> {
+        let mut conflicts = Vec::new();
+        for (i, a) in InputAction::ALL.iter().enumerate() {
+            for b in InputAction::ALL.iter().skip(i + 1) {
+                if !a.conflicts_with(*b) {
+                    continue;
+                }
+                let shared_key = self.keys.get(a).is_some_and(|keys| {
+                    self.keys
+                        .get(b)
+                        .is_some_and(|other| keys.iter().any(|key| other.contains(key)))
+                });
+                let shared_button = self.buttons.get(a).is_some_and(|buttons| {
+                    self.buttons
+                        .get(b)
+                        .is_some_and(|other| buttons.iter().any(|button| other.contains(button)))
+                });
+                if shared_key || shared_button {
+                    conflicts.push((*a, *b));
+                }
+            }
+        }
+        conflicts
+    }
+
+    //Actions added after the file was written keep their default bindings
+    fn fill_missing(&mut self, defaults: &InputBindings) {
+        for (action, keys) in defaults.keys.iter() {
+            self.keys.entry(*action).or_insert_with(|| keys.clone());
+        }
+        for (action, buttons) in defaults.buttons.iter() {
+            self.buttons
+                .entry(*action)
+                .or_insert_with(|| buttons.clone());
+        }
+    }
+}
+
+//An empty slot past the end gets the binding added instead
+fn set_slot<T: PartialEq>(bindings: &mut Vec<T>, slot: usize, binding: T) {
+    if bindings.contains(&binding) {
+        return;
+    }
+    match bindings.get_mut(slot) {
+        Some(old) => *old = binding,
+        None => bindings.push(binding),
+    }
+}
+
+pub fn load_bindings() -> InputBindings {
+    let defaults = InputBindings::default();
+    let contents = match fs::read_to_string(CONTROLS_PATH) {
+        Ok(contents) => contents,
+        Err(_) => return defaults,
+    };
+
+    match ron::from_str::<InputBindings>(&contents) {
+        Ok(mut bindings) => {
+            bindings.fill_missing(&defaults);
+            for (a, b) in bindings.conflicts() {
+                warn!(
+                    "{}: {} and {} share a binding",
+                    CONTROLS_PATH,
+                    a.name(),
+                    b.name()
+                );
+            }
+            bindings
+        }
+        Err(err) => {
+            warn!("Could not parse {}, using defaults: {}", CONTROLS_PATH, err);
+            defaults
+        }
+    }
+}
+
+pub fn save_bindings(bindings: &InputBindings) {
+    let contents = ron::ser::to_string_pretty(bindings, ron::ser::PrettyConfig::default())
+        .expect("Bindings should always serialize");
+    if let Err(err) = fs::write(CONTROLS_PATH, contents) {
+        warn!("Could not save {}: {}", CONTROLS_PATH, err);
+    }
+}
+
+#[derive(Default)]
+pub struct InputActions {
+    pressed: HashSet<InputAction>,
+    just_pressed: HashSet<InputAction>,
+}
+
+impl InputActions {
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn any_just_pressed(&self, actions: impl IntoIterator<Item = InputAction>) -> bool {
+        actions.into_iter().any(|action| self.just_pressed(action))
+    }
+
+    //Consumes every press of this frame, like Input::clear
+    pub fn clear(&mut self) {
+        self.just_pressed.clear();
+    }
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let mut keys = BTreeMap::new();
+        keys.insert(InputAction::MoveUp, vec![KeyCode::W]);
+        keys.insert(InputAction::MoveDown, vec![KeyCode::S]);
+        keys.insert(InputAction::MoveLeft, vec![KeyCode::A]);
+        keys.insert(InputAction::MoveRight, vec![KeyCode::D]);
+        keys.insert(InputAction::Confirm, vec![KeyCode::E]);
+        keys.insert(InputAction::Cancel, vec![KeyCode::Space, KeyCode::Escape]);
+        keys.insert(InputAction::MenuLeft, vec![KeyCode::A]);
+        keys.insert(InputAction::MenuRight, vec![KeyCode::D]);
+        keys.insert(InputAction::VolumeUp, vec![KeyCode::Up]);
+        keys.insert(InputAction::VolumeDown, vec![KeyCode::Down]);
+        keys.insert(InputAction::OpenControls, vec![KeyCode::F1]);
+        keys.insert(InputAction::ZoomIn, vec![KeyCode::Equals]);
+        keys.insert(InputAction::ZoomOut, vec![KeyCode::Minus]);
+        keys.insert(InputAction::ToggleTiles, vec![KeyCode::F2]);
+        keys.insert(InputAction::OpenMap, vec![KeyCode::M]);
+
+        let mut buttons = BTreeMap::new();
+        buttons.insert(InputAction::MoveUp, vec![GamepadButtonType::DPadUp]);
+        buttons.insert(InputAction::MoveDown, vec![GamepadButtonType::DPadDown]);
+        buttons.insert(InputAction::MoveLeft, vec![GamepadButtonType::DPadLeft]);
+        buttons.insert(InputAction::MoveRight, vec![GamepadButtonType::DPadRight]);
+        buttons.insert(InputAction::Confirm, vec![GamepadButtonType::South]);
+        buttons.insert(InputAction::Cancel, vec![GamepadButtonType::East]);
+        buttons.insert(InputAction::MenuLeft, vec![GamepadButtonType::DPadLeft]);
+        buttons.insert(InputAction::MenuRight, vec![GamepadButtonType::DPadRight]);
+        buttons.insert(InputAction::VolumeUp, vec![GamepadButtonType::RightTrigger]);
+        buttons.insert(
+            InputAction::VolumeDown,
+            vec![GamepadButtonType::LeftTrigger],
+        );
+        buttons.insert(InputAction::OpenControls, vec![GamepadButtonType::Start]);
+        buttons.insert(InputAction::ZoomIn, vec![GamepadButtonType::RightTrigger2]);
+        buttons.insert(InputAction::ZoomOut, vec![GamepadButtonType::LeftTrigger2]);
+        buttons.insert(InputAction::ToggleTiles, vec![GamepadButtonType::Select]);
+        buttons.insert(InputAction::OpenMap, vec![GamepadButtonType::North]);
+
+        InputBindings { keys, buttons }
+    }
+}
+
+impl Plugin for GameInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_bindings())
+            .init_resource::<InputActions>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_input_actions.after(InputSystem),
+            );
+    }
+}
+
+fn update_input_actions(
+    keyboard: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    bindings: Res<InputBindings>,
+    mut actions: ResMut<InputActions>,
+) {
+    let previous = std::mem::take(&mut actions.pressed);
+    actions.just_pressed.clear();
+
+    for (action, keys) in bindings.keys.iter() {
+        if keyboard.any_pressed(keys.iter().copied()) {
+            actions.pressed.insert(*action);
+        }
+        //Catches keys pressed and released within a single frame
+        if keyboard.any_just_pressed(keys.iter().copied()) {
+            actions.just_pressed.insert(*action);
+        }
+    }
+
+    for gamepad in gamepads.iter() {
+        for (action, buttons) in bindings.buttons.iter() {
+            if buttons
+                .iter()
+                .any(|button| gamepad_buttons.pressed(GamepadButton(*gamepad, *button)))
+            {
+                actions.pressed.insert(*action);
+            }
+        }
+
+        let stick_x = gamepad_axes
+            .get(GamepadAxis(*gamepad, GamepadAxisType::LeftStickX))
+            .unwrap_or(0.0);
+        let stick_y = gamepad_axes
+            .get(GamepadAxis(*gamepad, GamepadAxisType::LeftStickY))
+            .unwrap_or(0.0);
+
+        if stick_y > STICK_THRESHOLD {
+            actions.pressed.insert(InputAction::MoveUp);
+        }
+        if stick_y < -STICK_THRESHOLD {
+            actions.pressed.insert(InputAction::MoveDown);
+        }
+        if stick_x < -STICK_THRESHOLD {
+            actions.pressed.insert(InputAction::MoveLeft);
+            actions.pressed.insert(InputAction::MenuLeft);
+        }
+        if stick_x > STICK_THRESHOLD {
+            actions.pressed.insert(InputAction::MoveRight);
+            actions.pressed.insert(InputAction::MenuRight);
+        }
+    }
+
+    let newly_pressed: Vec<InputAction> = actions.pressed.difference(&previous).copied().collect();
+    actions.just_pressed.extend(newly_pressed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_read_together_conflict() {
+        assert!(InputAction::MoveLeft.conflicts_with(InputAction::ZoomIn));
+        assert!(InputAction::MenuLeft.conflicts_with(InputAction::MenuRight));
+        assert!(InputAction::Confirm.conflicts_with(InputAction::MoveUp));
+        assert!(InputAction::OpenMap.conflicts_with(InputAction::MenuLeft));
+        assert!(!InputAction::MoveLeft.conflicts_with(InputAction::MoveLeft));
+    }
+
+    #[test]
+    fn field_and_menu_actions_may_share_keys() {
+        assert!(!InputAction::MoveLeft.conflicts_with(InputAction::MenuLeft));
+        assert!(InputBindings::default().conflicts().is_empty());
+    }
+
+    #[test]
+    fn rebinding_replaces_only_the_selected_slot() {
+        let mut bindings = InputBindings::default();
+        bindings
+            .rebind_key(InputAction::Cancel, 0, KeyCode::Back)
+            .unwrap();
+        assert_eq!(
+            bindings.keys[&InputAction::Cancel],
+            vec![KeyCode::Back, KeyCode::Escape]
+        );
+        bindings
+            .rebind_key(InputAction::Confirm, 1, KeyCode::Return)
+            .unwrap();
+        assert_eq!(
+            bindings.keys[&InputAction::Confirm],
+            vec![KeyCode::E, KeyCode::Return]
+        );
+        bindings
+            .rebind_button(InputAction::Cancel, 1, GamepadButtonType::West)
+            .unwrap();
+        assert_eq!(
+            bindings.buttons[&InputAction::Cancel],
+            vec![GamepadButtonType::East, GamepadButtonType::West]
+        );
+    }
+
+    #[test]
+    fn rebinding_refuses_a_key_in_use() {
+        let mut bindings = InputBindings::default();
+        assert_eq!(
+            bindings.rebind_key(InputAction::ZoomIn, 0, KeyCode::W),
+            Err(InputAction::MoveUp)
+        );
+        assert_eq!(
+            bindings.rebind_button(InputAction::Confirm, 0, GamepadButtonType::East),
+            Err(InputAction::Cancel)
+        );
+        assert_eq!(bindings.keys[&InputAction::ZoomIn], vec![KeyCode::Equals]);
+        //Menus are closed while walking around, so they can reuse the movement keys
+        assert_eq!(
+            bindings.rebind_key(InputAction::MenuRight, 0, KeyCode::W),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn lists_bindings_shared_in_one_context() {
+        let mut bindings = InputBindings::default();
+        bindings.keys.insert(InputAction::ZoomIn, vec![KeyCode::W]);
+        bindings
+            .keys
+            .insert(InputAction::MenuLeft, vec![KeyCode::W]);
+        assert_eq!(
+            bindings.conflicts(),
+            vec![(InputAction::MoveUp, InputAction::ZoomIn)]
+        );
+    }
+}
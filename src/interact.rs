@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+use bevy_2d_rpg::collision::CollisionMap;
+
+use crate::{
+    ascii::AsciiSheet,
+    camera::CameraController,
+    input::{InputAction, InputActions},
+    npc::{in_interact_range, npc_speech, spawn_textbox},
+    player::Player,
+    save::{Inventory, WorldFlags},
+    tilemap::{sync_tile, world_to_tile, CurrentMap, MapChunks},
+    GameState, TILE_SIZE,
+};
+
+pub struct InteractPlugin;
+
+#[derive(Component, Clone)]
+pub enum Interactable {
+    Sign(String),
+    //Item handed out the first time the chest is opened
+    Chest(String),
+    Door,
+    //Tiles flipped every time the switch is used
+    Switch(Vec<(i32, i32)>),
+}
+
+impl Plugin for InteractPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Overworld)
+                .with_system(interact_with_objects.after(npc_speech)),
+        );
+    }
+}
+
+fn interact_with_objects(
+    mut commands: Commands,
+    mut player_query: Query<(&mut Player, &Transform)>,
+    object_query: Query<(&Interactable, &Transform)>,
+    mut chunks: ResMut<MapChunks>,
+    mut collision_map: ResMut<CollisionMap>,
+    mut flags: ResMut<WorldFlags>,
+    mut inventory: ResMut<Inventory>,
+    mut current_map: ResMut<CurrentMap>,
+    camera: Res<CameraController>,
+    actions: Res<InputActions>,
+    ascii: Res<AsciiSheet>,
+) {
+    let (mut player, player_transform) = player_query.single_mut();
+    if !player.active || !actions.just_pressed(InputAction::Confirm) {
+        return;
+    }
+
+    let target = object_query.iter().find(|(_, transform)| {
+        in_interact_range(transform.translation, player_transform.translation)
+    });
+    let (interactable, transform) = match target {
+        Some(target) => target,
+        None => return,
+    };
+    let position = world_to_tile(transform.translation);
+    let key = (current_map.name.clone(), position.0, position.1);
+
+    //Chests, doors and switches all change look through their toggle in the legend
+    let mut toggled = Vec::new();
+    let message = match interactable {
+        Interactable::Sign(text) => Some(text.clone()),
+        Interactable::Chest(item) => {
+            if flags.opened_chests.contains(&key) {
+                Some("The chest is empty".to_string())
+            } else {
+                flags.opened_chests.insert(key);
+                inventory.add(item);
+                toggled.push(position);
+                Some(format!("You found a {}!", item))
+            }
+        }
+        Interactable::Door => {
+            //Doors stay open so the player can't get shut in
+            if current_map
+                .data
+                .is_solid(&current_map.legend, position.0, position.1)
+            {
+                toggled.push(position);
+            }
+            None
+        }
+        Interactable::Switch(targets) => {
+            if !flags.switches_on.remove(&key) {
+                flags.switches_on.insert(key);
+            }
+            toggled.push(position);
+            toggled.extend(targets.iter().copied());
+            None
+        }
+    };
+
+    for position in toggled {
+        let CurrentMap { data, legend, .. } = &mut *current_map;
+        data.toggle(legend, position.0, position.1);
+        sync_tile(&mut chunks, &mut collision_map, &current_map, position);
+    }
+
+    if let Some(message) = message {
+        player.active = false;
+        spawn_textbox(
+            &mut commands,
+            &ascii,
+            camera.screen_transform(Vec2::new(0.0, 1.0 - 1.5 * TILE_SIZE), 960.0),
+            &message,
+        );
+    }
+}
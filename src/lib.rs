@@ -0,0 +1,8 @@
+//Pieces of the game that don't need a running App, shared with benches and tools
+pub mod collision;
+pub mod dungeon;
+pub mod map;
+pub mod pathfinding;
+pub mod quads;
+pub mod sight;
+pub mod tiled;
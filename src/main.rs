@@ -1,5 +1,6 @@
 #![allow(clippy::redundant_field_names)]
 use bevy::{
+    asset::AssetServerSettings,
     prelude::*,
     reflect::erased_serde::__private::serde::__private::de,
     render::{camera::ScalingMode, render_resource::Texture},
@@ -12,23 +13,47 @@ pub const TILE_SIZE: f32 = 0.1;
 
 mod ascii;
 mod audio;
+mod batch;
+mod camera;
+mod clock;
 mod combat;
+mod controls;
 mod debug;
+mod editor;
+mod encounter;
 mod fadeout;
+mod fog;
 mod graphics;
+mod input;
+mod interact;
+mod map_asset;
+mod minimap;
 mod npc;
 mod player;
+mod roamer;
+mod save;
 mod start_menu;
 mod tilemap;
 
 use ascii::AsciiPlugin;
 use audio::GameAudioPlugin;
+use batch::BatchPlugin;
+use camera::CameraPlugin;
+use clock::ClockPlugin;
 use combat::CombatPlugin;
+use controls::ControlsPlugin;
 use debug::DebugPlugin;
+use encounter::EncounterPlugin;
 use fadeout::FadeoutPlugin;
+use fog::FogPlugin;
 use graphics::GraphicsPlugin;
+use input::GameInputPlugin;
+use interact::InteractPlugin;
+use minimap::MinimapPlugin;
 use npc::NpcPlugin;
 use player::PlayerPlugin;
+use roamer::RoamerPlugin;
+use save::SavePlugin;
 use start_menu::MainMenuPlugin;
 use tilemap::TileMapPlugin;
 
@@ -37,6 +62,8 @@ pub enum GameState {
     StartMenu,
     Overworld,
     Combat,
+    Controls,
+    WorldMap,
 }
 
 fn main() {
@@ -51,9 +78,20 @@ fn main() {
             resizable: false,
             ..Default::default()
         })
+        //Lets maps and other assets be edited while the game runs
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: cfg!(debug_assertions),
+            ..Default::default()
+        })
         .add_startup_system(spawn_camera)
         .add_plugins(DefaultPlugins)
+        .add_plugin(GameInputPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(EncounterPlugin)
         .add_plugin(PlayerPlugin)
+        .add_plugin(CameraPlugin)
+        .add_plugin(ClockPlugin)
+        .add_plugin(BatchPlugin)
         .add_plugin(AsciiPlugin)
         .add_plugin(GameAudioPlugin)
         .add_plugin(TileMapPlugin)
@@ -62,6 +100,11 @@ fn main() {
         .add_plugin(GraphicsPlugin)
         .add_plugin(MainMenuPlugin)
         .add_plugin(NpcPlugin)
+        .add_plugin(InteractPlugin)
+        .add_plugin(RoamerPlugin)
+        .add_plugin(ControlsPlugin)
+        .add_plugin(FogPlugin)
+        .add_plugin(MinimapPlugin)
         .add_plugin(DebugPlugin)
         .run();
 }
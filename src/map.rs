@@ -0,0 +1,463 @@
+use std::{collections::HashMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::collision::{CollisionMap, TileFlags};
+
+//Glyph used for "nothing here" in every layer
+pub const EMPTY: char = ' ';
+//Name of the spawn marked with a Spawn glyph, used when a map is entered without a named spawn
+pub const DEFAULT_SPAWN: &str = "default";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ObjectType {
+    Sign,
+    Chest,
+    Door,
+    Switch,
+    Npc,
+    //Where the player starts, never drawn
+    Spawn,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegendEntry {
+    pub name: String,
+    //Index into the ascii sheet, the glyph itself when left out
+    #[serde(default)]
+    pub sprite: Option<usize>,
+    pub color: (f32, f32, f32),
+    #[serde(default)]
+    pub collider: bool,
+    //Name of an encounter zone from encounter_zones.ron
+    #[serde(default)]
+    pub zone: Option<String>,
+    #[serde(default)]
+    pub object: Option<ObjectType>,
+    //What the glyph turns into when opened or switched, the way back is its own entry
+    #[serde(default)]
+    pub toggle: Option<char>,
+    //Image under assets/ and index for the graphical tile sheets
+    #[serde(default)]
+    pub tile: Option<(String, usize)>,
+    //Cycles the tile through other indices of the same sheet
+    #[serde(default)]
+    pub animation: Option<TileAnimation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TileAnimation {
+    pub frames: Vec<usize>,
+    //Frames per second
+    pub fps: f32,
+}
+
+impl TileAnimation {
+    //Every tile with the same animation shows the same frame at the same time
+    pub fn frame(&self, seconds: f32) -> usize {
+        if self.frames.is_empty() {
+            return 0;
+        }
+        let step = (seconds * self.fps).max(0.0) as usize;
+        self.frames[step % self.frames.len()]
+    }
+}
+
+//What every glyph means, shared by all maps
+//Written as a plain map of glyphs in legend.ron
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Legend(pub HashMap<char, LegendEntry>);
+
+impl Legend {
+    pub fn from_ron(contents: &str) -> Result<Legend, ron::Error> {
+        ron::from_str(contents)
+    }
+
+    pub fn get(&self, glyph: char) -> Option<&LegendEntry> {
+        self.0.get(&glyph)
+    }
+
+    pub fn sprite(&self, glyph: char) -> usize {
+        self.get(glyph)
+            .and_then(|entry| entry.sprite)
+            .unwrap_or(glyph as usize)
+    }
+
+    pub fn toggled(&self, glyph: char) -> Option<char> {
+        self.get(glyph).and_then(|entry| entry.toggle)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MapLayer {
+    Ground,
+    Decoration,
+    Collision,
+    Objects,
+}
+
+impl MapLayer {
+    pub const ALL: [MapLayer; 4] = [
+        MapLayer::Ground,
+        MapLayer::Decoration,
+        MapLayer::Collision,
+        MapLayer::Objects,
+    ];
+    //The layers that are drawn, bottom to top
+    pub const VISIBLE: [MapLayer; 3] = [MapLayer::Ground, MapLayer::Decoration, MapLayer::Objects];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MapLayer::Ground => "ground",
+            MapLayer::Decoration => "decoration",
+            MapLayer::Collision => "collision",
+            MapLayer::Objects => "objects",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<MapLayer> {
+        MapLayer::ALL
+            .iter()
+            .copied()
+            .find(|layer| layer.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for MapError {}
+
+//The name of a [layer] header line, other lines starting with [ are rows
+pub fn layer_header(line: &str) -> Option<&str> {
+    let name = line.trim_end().strip_prefix('[')?.strip_suffix(']')?;
+    (!name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase())).then_some(name)
+}
+
+//A map file is a list of sections, each a [layer] header followed by its rows:
+//  ground      terrain and the size of the map, the only required layer, blank tiles are void
+//  decoration  drawn over the ground, blank tiles show the ground
+//  collision   '#' forces a wall, '.' forces a walkable tile, blank uses the legend
+//  objects     chests, signs, doors, switches, NPCs and warp markers
+#[derive(Debug, Clone, Default)]
+pub struct MapData {
+    width: usize,
+    height: usize,
+    layers: HashMap<MapLayer, Vec<char>>,
+}
+
+impl MapData {
+    pub fn new(width: usize, height: usize) -> Self {
+        let layers = MapLayer::ALL
+            .iter()
+            .map(|layer| (*layer, vec![EMPTY; width * height]))
+            .collect();
+        MapData {
+            width,
+            height,
+            layers,
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<MapData, MapError> {
+        //Rows are kept with their line number for error messages
+        let mut sections: Vec<(MapLayer, Vec<(usize, &str)>)> = Vec::new();
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            if let Some(name) = layer_header(line) {
+                let layer = MapLayer::from_name(name).ok_or_else(|| MapError {
+                    line: line_number,
+                    column: 2,
+                    message: format!("unknown layer \"{}\"", name),
+                })?;
+                if sections.iter().any(|(existing, _)| *existing == layer) {
+                    return Err(MapError {
+                        line: line_number,
+                        column: 2,
+                        message: format!("layer \"{}\" appears twice", name),
+                    });
+                }
+                sections.push((layer, Vec::new()));
+                continue;
+            }
+            match sections.last_mut() {
+                Some((_, rows)) => rows.push((line_number, line)),
+                None if line.trim().is_empty() => {}
+                None => {
+                    return Err(MapError {
+                        line: line_number,
+                        column: line.chars().take_while(|c| c.is_whitespace()).count() + 1,
+                        message: "rows before the first [layer] header".to_string(),
+                    })
+                }
+            }
+        }
+
+        //Trailing blank lines would otherwise count as rows
+        for (_, rows) in sections.iter_mut() {
+            while rows.last().is_some_and(|(_, row)| row.trim().is_empty()) {
+                rows.pop();
+            }
+        }
+        let ground = match sections
+            .iter()
+            .find(|(layer, _)| *layer == MapLayer::Ground)
+        {
+            Some((_, rows)) => rows,
+            None => {
+                return Err(MapError {
+                    line: contents.lines().count(),
+                    column: 1,
+                    message: "missing [ground] layer".to_string(),
+                })
+            }
+        };
+
+        //Rows may be ragged since trailing void is trimmed on save,
+        //but nothing may lie outside of the ground
+        let width = ground
+            .iter()
+            .map(|(_, row)| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        let height = ground.len();
+        for (layer, rows) in sections.iter() {
+            for (y, (line, row)) in rows.iter().enumerate() {
+                let outside = row
+                    .chars()
+                    .enumerate()
+                    .find(|(x, glyph)| *glyph != EMPTY && (*x >= width || y >= height));
+                if let Some((x, glyph)) = outside {
+                    return Err(MapError {
+                        line: *line,
+                        column: x + 1,
+                        message: format!(
+                            "{:?} in layer \"{}\" is outside of the ground",
+                            glyph,
+                            layer.name()
+                        ),
+                    });
+                }
+            }
+        }
+
+        let mut map = MapData::new(width, height);
+        for (layer, rows) in sections {
+            for (y, (_, row)) in rows.iter().enumerate() {
+                for (x, glyph) in row.chars().enumerate() {
+                    map.set(layer, x as i32, y as i32, glyph);
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    //Blank trailing tiles are trimmed so files stay tidy
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for layer in MapLayer::ALL {
+            if layer != MapLayer::Ground && self.layer_is_empty(layer) {
+                continue;
+            }
+            text.push_str(&format!("[{}]\n", layer.name()));
+            for y in 0..self.height as i32 {
+                let row: String = (0..self.width as i32)
+                    .map(|x| self.get(layer, x, y))
+                    .collect();
+                text.push_str(row.trim_end());
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        Some(y as usize * self.width + x as usize)
+    }
+
+    pub fn get(&self, layer: MapLayer, x: i32, y: i32) -> char {
+        match self.index(x, y) {
+            Some(i) => self.layers[&layer][i],
+            None => EMPTY,
+        }
+    }
+
+    pub fn set(&mut self, layer: MapLayer, x: i32, y: i32, glyph: char) {
+        if let Some(i) = self.index(x, y) {
+            if let Some(tiles) = self.layers.get_mut(&layer) {
+                tiles[i] = glyph;
+            }
+        }
+    }
+
+    //Flips the topmost glyph that has a toggle, used by chests, doors and switch targets
+    pub fn toggle(&mut self, legend: &Legend, x: i32, y: i32) -> Option<(MapLayer, char)> {
+        for layer in MapLayer::VISIBLE.iter().rev() {
+            if let Some(glyph) = legend.toggled(self.get(*layer, x, y)) {
+                self.set(*layer, x, y, glyph);
+                return Some((*layer, glyph));
+            }
+        }
+        None
+    }
+
+    //Positions of every glyph in the objects layer standing for the given object
+    pub fn find_objects<'a>(
+        &'a self,
+        legend: &'a Legend,
+        object: ObjectType,
+    ) -> impl Iterator<Item = (i32, i32)> + 'a {
+        self.glyphs(MapLayer::Objects)
+            .filter(move |(_, glyph)| {
+                legend
+                    .get(*glyph)
+                    .is_some_and(|entry| entry.object == Some(object))
+            })
+            .map(|(position, _)| position)
+    }
+
+    pub fn layer_is_empty(&self, layer: MapLayer) -> bool {
+        self.layers[&layer].iter().all(|glyph| *glyph == EMPTY)
+    }
+
+    //Every non blank glyph of a layer with its position
+    pub fn glyphs(&self, layer: MapLayer) -> impl Iterator<Item = ((i32, i32), char)> + '_ {
+        let width = self.width.max(1);
+        self.layers[&layer]
+            .iter()
+            .enumerate()
+            .filter(|(_, glyph)| **glyph != EMPTY)
+            .map(move |(i, glyph)| (((i % width) as i32, (i / width) as i32), *glyph))
+    }
+
+    //Glyphs the legend doesn't know are treated as walls so broken maps can't be walked out of
+    pub fn is_solid(&self, legend: &Legend, x: i32, y: i32) -> bool {
+        match self.get(MapLayer::Collision, x, y) {
+            '#' => return true,
+            '.' => return false,
+            _ => {}
+        }
+        if self.get(MapLayer::Ground, x, y) == EMPTY {
+            return true;
+        }
+        MapLayer::VISIBLE.iter().any(|layer| {
+            let glyph = self.get(*layer, x, y);
+            glyph != EMPTY && legend.get(glyph).is_none_or(|entry| entry.collider)
+        })
+    }
+
+    //The topmost layer naming a zone wins
+    pub fn zone<'a>(&self, legend: &'a Legend, x: i32, y: i32) -> Option<&'a str> {
+        [MapLayer::Decoration, MapLayer::Ground]
+            .iter()
+            .filter_map(|layer| legend.get(self.get(*layer, x, y)))
+            .find_map(|entry| entry.zone.as_deref())
+    }
+
+    pub fn collision_map(
+        &self,
+        legend: &Legend,
+        zone_id: impl Fn(&str) -> Option<usize>,
+    ) -> CollisionMap {
+        let mut collision_map = CollisionMap::new(self.width, self.height);
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                collision_map.set(
+                    x,
+                    y,
+                    TileFlags {
+                        solid: self.is_solid(legend, x, y),
+                        encounter_zone: self.zone(legend, x, y).and_then(&zone_id),
+                    },
+                );
+            }
+        }
+        collision_map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_at(contents: &str) -> (usize, usize) {
+        let err = MapData::parse(contents).unwrap_err();
+        (err.line, err.column)
+    }
+
+    #[test]
+    fn parses_layers() {
+        let map = MapData::parse("[ground]\n###\n#.#\n\n[objects]\n\n $\n").unwrap();
+        assert_eq!((map.width(), map.height()), (3, 2));
+        assert_eq!(map.get(MapLayer::Ground, 1, 1), '.');
+        assert_eq!(map.get(MapLayer::Objects, 1, 1), '$');
+    }
+
+    #[test]
+    fn ragged_rows_are_padded_with_void() {
+        let map = MapData::parse("[ground]\n###\n#\n").unwrap();
+        assert_eq!(map.width(), 3);
+        assert_eq!(map.get(MapLayer::Ground, 2, 1), EMPTY);
+    }
+
+    #[test]
+    fn reports_unknown_section() {
+        assert_eq!(error_at("[ground]\n#\n[roof]\n#\n"), (3, 2));
+    }
+
+    #[test]
+    fn reports_duplicate_section() {
+        assert_eq!(error_at("[ground]\n#\n\n[ground]\n#\n"), (4, 2));
+    }
+
+    #[test]
+    fn reports_rows_before_header() {
+        assert_eq!(error_at("\n  ##\n[ground]\n#\n"), (2, 3));
+    }
+
+    #[test]
+    fn reports_missing_ground() {
+        assert_eq!(error_at("[objects]\n$\n"), (2, 1));
+    }
+
+    #[test]
+    fn reports_row_past_the_ground() {
+        assert_eq!(error_at("[ground]\n##\n##\n[decoration]\n\n.. ,\n"), (6, 4));
+        assert_eq!(error_at("[ground]\n##\n[objects]\n\n $\n"), (5, 2));
+    }
+
+    #[test]
+    fn bracket_rows_are_not_headers() {
+        let map = MapData::parse("[ground]\n[#]#\n[..\n").unwrap();
+        assert_eq!(map.height(), 2);
+        assert_eq!(map.get(MapLayer::Ground, 0, 1), '[');
+        assert_eq!(layer_header("[ground]  "), Some("ground"));
+        assert_eq!(layer_header("[#]#"), None);
+    }
+}
@@ -0,0 +1,103 @@
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    reflect::TypeUuid,
+};
+use bevy_2d_rpg::{
+    map::MapData,
+    tiled::{TiledMap, TilesetFiles},
+};
+use serde::de::DeserializeOwned;
+
+//A parsed .map file, reloaded by the asset server whenever the file is saved
+#[derive(TypeUuid)]
+#[uuid = "6f1c2a4e-93d7-4b8e-a5f0-2c7d8e91b3a6"]
+pub struct MapAsset(pub MapData);
+
+#[derive(Default)]
+pub struct MapAssetLoader;
+
+impl AssetLoader for MapAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let contents = std::str::from_utf8(bytes)?;
+            //The asset server logs the error with the file's path, so only the position is added
+            let data = MapData::parse(contents)?;
+            load_context.set_default_asset(LoadedAsset::new(MapAsset(data)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map"]
+    }
+}
+
+//A map saved by the Tiled editor, with its external tilesets read through the asset server
+#[derive(TypeUuid)]
+#[uuid = "b84d0e57-1a3c-4f62-9e2b-7c05d6a8f413"]
+pub struct TiledAsset(pub TiledMap);
+
+#[derive(Default)]
+pub struct TiledAssetLoader;
+
+impl AssetLoader for TiledAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().to_path_buf();
+            let contents = std::str::from_utf8(bytes)?;
+            let mut files = TilesetFiles::new();
+            for source in TiledMap::tileset_sources(contents, &path)? {
+                let tileset = load_context.read_asset_bytes(&source).await?;
+                files.insert(source, String::from_utf8(tileset)?);
+            }
+            let map = TiledMap::parse(contents, &path, &files)?;
+            load_context.set_default_asset(LoadedAsset::new(TiledAsset(map)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tmj", "tmx"]
+    }
+}
+
+//The text of a .ron data file. One loader serves every .ron, so whoever reads the file
+//parses it into their own type and reports a malformed file without panicking
+#[derive(TypeUuid)]
+#[uuid = "3e9a7c21-5d48-4b0f-8f6e-d12b97c4a058"]
+pub struct RonAsset(pub String);
+
+impl RonAsset {
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, ron::Error> {
+        ron::from_str(&self.0)
+    }
+}
+
+#[derive(Default)]
+pub struct RonAssetLoader;
+
+impl AssetLoader for RonAssetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let contents = String::from_utf8(bytes.to_vec())?;
+            load_context.set_default_asset(LoadedAsset::new(RonAsset(contents)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
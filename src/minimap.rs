@@ -0,0 +1,273 @@
+use std::collections::HashSet;
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{
+        Extent3d, FilterMode, SamplerDescriptor, TextureDimension, TextureFormat,
+    },
+    transform::TransformSystem,
+};
+use bevy_2d_rpg::map::{MapLayer, ObjectType, EMPTY};
+
+use crate::{
+    ascii::spawn_ascii_text,
+    camera::CameraController,
+    input::{InputAction, InputActions},
+    npc::Npc,
+    player::Player,
+    save::ExploredMaps,
+    tilemap::{world_to_tile, CurrentMap},
+    GameState, RESOLUTION, TILE_SIZE,
+};
+
+pub struct MinimapPlugin;
+
+//Tiles shown around the player in each direction
+const MINIMAP_RADIUS: i32 = 12;
+//Side of the minimap in unzoomed screen units
+const MINIMAP_SIZE: f32 = 0.5;
+const MINIMAP_Z: f32 = 955.0;
+//Unexplored tiles on the full map are drawn at this brightness
+const UNEXPLORED_DIM: f32 = 0.35;
+
+const BACKGROUND: [u8; 4] = [0, 0, 0, 160];
+const PLAYER_MARKER: [u8; 4] = [255, 255, 255, 255];
+const NPC_MARKER: [u8; 4] = [250, 220, 60, 255];
+
+#[derive(Component)]
+struct Minimap(Handle<Image>);
+
+#[derive(Component)]
+struct WorldMapScreen;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::Overworld).with_system(spawn_minimap))
+            .add_system_set(
+                SystemSet::on_update(GameState::Overworld)
+                    .with_system(draw_minimap)
+                    .with_system(open_world_map),
+            )
+            .add_system_set(SystemSet::on_pause(GameState::Overworld).with_system(hide_minimap))
+            .add_system_set(SystemSet::on_resume(GameState::Overworld).with_system(show_minimap))
+            .add_system_set(SystemSet::on_enter(GameState::WorldMap).with_system(spawn_world_map))
+            .add_system_set(SystemSet::on_update(GameState::WorldMap).with_system(close_world_map))
+            .add_system_set(SystemSet::on_exit(GameState::WorldMap).with_system(despawn_world_map))
+            //Placed after the camera moved so it doesn't lag a frame behind
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                place_minimap.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+//What a tile looks like from above, the colour of its topmost glyph
+fn tile_pixel(current_map: &CurrentMap, tile: (i32, i32)) -> [u8; 4] {
+    let legend = &current_map.legend;
+    for layer in MapLayer::VISIBLE.iter().rev() {
+        let glyph = current_map.data.get(*layer, tile.0, tile.1);
+        if glyph == EMPTY {
+            continue;
+        }
+        let entry = match legend.get(glyph) {
+            Some(entry) => entry,
+            None => return [255, 0, 255, 255],
+        };
+        if matches!(entry.object, Some(ObjectType::Npc | ObjectType::Spawn)) {
+            continue;
+        }
+        let (r, g, b) = entry.color;
+        return [(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8, 255];
+    }
+    BACKGROUND
+}
+
+//One pixel per tile, drawn without smoothing so tiles stay square when scaled up
+fn map_image(width: usize, height: usize, pixel: impl Fn((i32, i32)) -> [u8; 4]) -> Image {
+    let mut data = Vec::with_capacity(width * height * 4);
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            data.extend(pixel((x, y)));
+        }
+    }
+    let mut image = Image::new(
+        Extent3d {
+            width: width.max(1) as u32,
+            height: height.max(1) as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler_descriptor = SamplerDescriptor {
+        mag_filter: FilterMode::Nearest,
+        min_filter: FilterMode::Nearest,
+        ..Default::default()
+    };
+    image
+}
+
+fn spawn_minimap(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let side = (MINIMAP_RADIUS * 2 + 1) as usize;
+    let image = images.add(map_image(side, side, |_| BACKGROUND));
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(MINIMAP_SIZE)),
+                ..Default::default()
+            },
+            texture: image.clone(),
+            ..Default::default()
+        })
+        .insert(Minimap(image))
+        .insert(Name::new("Minimap"));
+}
+
+//Redrawn every frame around the player, it is only a few hundred pixels
+fn draw_minimap(
+    minimap_query: Query<&Minimap>,
+    player_query: Query<&Transform, With<Player>>,
+    npc_query: Query<&Transform, With<Npc>>,
+    current_map: Res<CurrentMap>,
+    explored: Res<ExploredMaps>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let minimap = match minimap_query.get_single() {
+        Ok(minimap) => minimap,
+        Err(_) => return,
+    };
+    let explored = explored.maps.get(&current_map.name);
+    let center = world_to_tile(player_query.single().translation);
+    let npcs: HashSet<(i32, i32)> = npc_query
+        .iter()
+        .map(|transform| world_to_tile(transform.translation))
+        .collect();
+
+    let side = (MINIMAP_RADIUS * 2 + 1) as usize;
+    let image = map_image(side, side, |(x, y)| {
+        let tile = (center.0 - MINIMAP_RADIUS + x, center.1 - MINIMAP_RADIUS + y);
+        if tile == center {
+            PLAYER_MARKER
+        } else if !explored.is_some_and(|tiles| tiles.contains(&tile)) {
+            BACKGROUND
+        } else if npcs.contains(&tile) {
+            NPC_MARKER
+        } else {
+            tile_pixel(&current_map, tile)
+        }
+    });
+    if let Some(old) = images.get_mut(&minimap.0) {
+        *old = image;
+    }
+}
+
+fn place_minimap(
+    camera: Res<CameraController>,
+    mut minimap_query: Query<&mut Transform, With<Minimap>>,
+) {
+    //Top right corner with a small margin
+    let offset = Vec2::new(RESOLUTION, 1.0) - Vec2::splat(MINIMAP_SIZE / 2.0 + TILE_SIZE / 2.0);
+    for mut transform in minimap_query.iter_mut() {
+        *transform = camera.screen_transform(offset, MINIMAP_Z);
+    }
+}
+
+fn hide_minimap(mut minimap_query: Query<&mut Visibility, With<Minimap>>) {
+    for mut visibility in minimap_query.iter_mut() {
+        visibility.is_visible = false;
+    }
+}
+
+fn show_minimap(mut minimap_query: Query<&mut Visibility, With<Minimap>>) {
+    for mut visibility in minimap_query.iter_mut() {
+        visibility.is_visible = true;
+    }
+}
+
+fn open_world_map(
+    player_query: Query<&Player>,
+    mut actions: ResMut<InputActions>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if player_query.single().active && actions.just_pressed(InputAction::OpenMap) {
+        //The screen would otherwise read the same press and close again
+        actions.clear();
+        let _ = state.push(GameState::WorldMap);
+    }
+}
+
+//The whole map scaled to fit the screen, with the parts not yet explored dimmed.
+//Dark maps only show what has been explored
+fn spawn_world_map(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    player_query: Query<&Transform, With<Player>>,
+    current_map: Res<CurrentMap>,
+    explored: Res<ExploredMaps>,
+    camera: Res<CameraController>,
+) {
+    let (width, height) = (current_map.data.width(), current_map.data.height());
+    let player = world_to_tile(player_query.single().translation);
+    let explored = explored.maps.get(&current_map.name);
+    let image = images.add(map_image(width, height, |tile| {
+        if tile == player {
+            return PLAYER_MARKER;
+        }
+        if explored.is_some_and(|tiles| tiles.contains(&tile)) {
+            return tile_pixel(&current_map, tile);
+        }
+        if current_map.dark {
+            return BACKGROUND;
+        }
+        let [r, g, b, a] = tile_pixel(&current_map, tile);
+        let dim = |channel: u8| (channel as f32 * UNEXPLORED_DIM) as u8;
+        [dim(r), dim(g), dim(b), a]
+    }));
+
+    //Leaves room for the title above
+    let space = Vec2::new(RESOLUTION * 2.0, 2.0) * 0.85;
+    let scale = (space.x / width.max(1) as f32).min(space.y / height.max(1) as f32);
+    let map = commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(width as f32, height as f32) * scale),
+                ..Default::default()
+            },
+            texture: image,
+            transform: Transform::from_xyz(0.0, -TILE_SIZE / 2.0, 0.0),
+            ..Default::default()
+        })
+        .id();
+    let title = format!("Map - {}", current_map.name);
+    let text = spawn_ascii_text(
+        &mut commands,
+        &title,
+        Vec3::new(
+            -(title.len() as f32 - 1.0) * TILE_SIZE / 2.0,
+            1.0 - TILE_SIZE,
+            0.0,
+        ),
+    );
+
+    commands
+        .spawn()
+        .insert(WorldMapScreen)
+        .insert(Name::new("World Map Screen"))
+        .insert(camera.screen_transform(Vec2::ZERO, 900.0))
+        .insert(GlobalTransform::default())
+        .push_children(&[map, text]);
+}
+
+fn close_world_map(mut actions: ResMut<InputActions>, mut state: ResMut<State<GameState>>) {
+    if actions.any_just_pressed([InputAction::Cancel, InputAction::OpenMap]) {
+        actions.clear();
+        let _ = state.pop();
+    }
+}
+
+fn despawn_world_map(mut commands: Commands, screen_query: Query<Entity, With<WorldMapScreen>>) {
+    for screen in screen_query.iter() {
+        commands.entity(screen).despawn_recursive();
+    }
+}
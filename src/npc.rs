@@ -1,14 +1,54 @@
-use bevy::{prelude::*, render::camera::Camera2d};
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_2d_rpg::{collision::CollisionMap, pathfinding::find_path};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    ascii::{spawn_ascii_sprite, spawn_ascii_text, spawn_nine_slice, AsciiSheet, NineSliceIndices},
+    ascii::{spawn_ascii_sprite, spawn_ascii_text, spawn_nine_slice, AsciiSheet},
+    camera::CameraController,
+    clock::{GameClock, TimeOfDay},
     combat::CombatStats,
-    player::Player,
+    graphics::{CharacterSheet, FacingDirection, FrameAnimation, NpcGraphics},
+    input::{InputAction, InputActions},
+    player::{world_to_tile_space, Player, PLAYER_HALF_SIZE},
+    tilemap::tile_to_world,
     GameState, MainCamera, CLEAR, TILE_SIZE,
 };
 
 pub struct NpcPlugin;
 
+//Tiles per second
+const NPC_SPEED: f32 = 2.0;
+//Pause between patrol legs and wander trips
+const NPC_WAIT: f32 = 1.5;
+
+//Set per NPC position in the map's .ron file
+#[derive(Clone, Serialize, Deserialize)]
+pub enum NpcMovement {
+    Still,
+    //Waypoints walked in a loop
+    Patrol(Vec<(i32, i32)>),
+    //Tiles away from where the NPC was placed
+    Wander(i32),
+}
+
+//Walks tile to tile, reserving the tile it steps into in the CollisionMap
+#[derive(Component)]
+pub struct NpcMover {
+    movement: NpcMovement,
+    home: (i32, i32),
+    tile: (i32, i32),
+    next: Option<(i32, i32)>,
+    progress: f32,
+    goal: Option<(i32, i32)>,
+    path: VecDeque<(i32, i32)>,
+    patrol_index: usize,
+    wait: Timer,
+    talking: bool,
+}
+
 #[derive(Component)]
 pub struct NpcText;
 
@@ -21,36 +61,212 @@ impl Plugin for NpcPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(
             SystemSet::on_update(GameState::Overworld)
-                .with_system(npc_speech)
+                .with_system(npc_movement)
+                .with_system(npc_speech.after(npc_movement))
                 .with_system(clear_speech.after(npc_speech)),
         );
     }
 }
 
+pub fn spawn_npc(
+    commands: &mut Commands,
+    characters: &CharacterSheet,
+    position: (i32, i32),
+    movement: NpcMovement,
+) -> Entity {
+    commands
+        .spawn_bundle(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: characters.npc_down[0],
+                custom_size: Some(Vec2::splat(TILE_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_translation(tile_to_world(position, 800.0)),
+            texture_atlas: characters.handle.clone(),
+            ..default()
+        })
+        .insert(FrameAnimation {
+            timer: Timer::from_seconds(0.2, true),
+            frames: characters.npc_down.to_vec(),
+            current_frame: 0,
+        })
+        .insert(NpcGraphics {
+            facing: FacingDirection::Down,
+        })
+        .insert(Name::new("Npc"))
+        .insert(Npc::Healer)
+        .insert(NpcMover {
+            movement,
+            home: position,
+            tile: position,
+            next: None,
+            progress: 0.0,
+            goal: None,
+            path: VecDeque::new(),
+            patrol_index: 0,
+            wait: Timer::from_seconds(NPC_WAIT, false),
+            talking: false,
+        })
+        .id()
+}
+
+fn facing_towards(from: Vec2, to: Vec2) -> FacingDirection {
+    let delta = to - from;
+    if delta.x.abs() > delta.y.abs() {
+        if delta.x > 0.0 {
+            FacingDirection::Right
+        } else {
+            FacingDirection::Left
+        }
+    } else if delta.y > 0.0 {
+        FacingDirection::Up
+    } else {
+        FacingDirection::Down
+    }
+}
+
+//Free movement lets the player straddle tiles, so their whole hitbox is checked
+fn player_covers(player: Vec2, tile: (i32, i32)) -> bool {
+    let reach = 0.5 + PLAYER_HALF_SIZE;
+    (player.x - tile.0 as f32).abs() < reach && (player.y - tile.1 as f32).abs() < reach
+}
+
+impl NpcMover {
+    fn next_goal(&mut self, collision_map: &CollisionMap) -> Option<(i32, i32)> {
+        match &self.movement {
+            NpcMovement::Still => None,
+            NpcMovement::Patrol(points) => {
+                let point = *points.get(self.patrol_index % points.len().max(1))?;
+                self.patrol_index = (self.patrol_index + 1) % points.len();
+                Some(point)
+            }
+            NpcMovement::Wander(radius) => {
+                //A negative radius in a map file would make an empty range
+                let radius = radius.abs();
+                let mut rng = thread_rng();
+                let goal = (
+                    self.home.0 + rng.gen_range(-radius..=radius),
+                    self.home.1 + rng.gen_range(-radius..=radius),
+                );
+                if collision_map.is_solid(goal.0, goal.1) {
+                    None
+                } else {
+                    Some(goal)
+                }
+            }
+        }
+    }
+}
+
+fn npc_movement(
+    mut npc_query: Query<(&mut NpcMover, &mut Transform, &mut NpcGraphics)>,
+    player_query: Query<&Transform, (With<Player>, Without<NpcMover>)>,
+    mut collision_map: ResMut<CollisionMap>,
+    time: Res<Time>,
+) {
+    let player = world_to_tile_space(player_query.single().translation);
+
+    for (mut mover, mut transform, mut graphics) in npc_query.iter_mut() {
+        if mover.talking {
+            continue;
+        }
+
+        //Finish the current step first
+        if let Some(next) = mover.next {
+            mover.progress += NPC_SPEED * time.delta_seconds();
+            let z = transform.translation.z;
+            if mover.progress >= 1.0 {
+                collision_map.set_solid(mover.tile.0, mover.tile.1, false);
+                transform.translation = tile_to_world(next, z);
+                mover.tile = next;
+                mover.next = None;
+            } else {
+                transform.translation =
+                    tile_to_world(mover.tile, z).lerp(tile_to_world(next, z), mover.progress);
+            }
+            continue;
+        }
+
+        if mover.path.is_empty() {
+            mover.wait.tick(time.delta());
+            if !mover.wait.finished() {
+                continue;
+            }
+            if mover.goal.is_none() {
+                mover.goal = mover.next_goal(&collision_map);
+            }
+            let goal = match mover.goal {
+                Some(goal) => goal,
+                None => continue,
+            };
+            match find_path(&collision_map, mover.tile, goal) {
+                Some(path) if !path.is_empty() => mover.path = path.into(),
+                //Already there or unreachable for now, pick something else later
+                _ => {
+                    mover.goal = None;
+                    mover.wait.reset();
+                    continue;
+                }
+            }
+        }
+
+        let step = match mover.path.front() {
+            Some(step) => *step,
+            None => continue,
+        };
+        //Someone moved into the way, wait a moment and plan the same trip again
+        if collision_map.is_solid(step.0, step.1) || player_covers(player, step) {
+            mover.path.clear();
+            mover.wait.reset();
+            continue;
+        }
+
+        mover.path.pop_front();
+        if mover.path.is_empty() {
+            mover.goal = None;
+            mover.wait.reset();
+        }
+        graphics.facing = facing_towards(
+            Vec2::new(mover.tile.0 as f32, -mover.tile.1 as f32),
+            Vec2::new(step.0 as f32, -step.1 as f32),
+        );
+        collision_map.set_solid(step.0, step.1, true);
+        mover.next = Some(step);
+        mover.progress = 0.0;
+    }
+}
+
 fn clear_speech(
     mut commands: Commands,
     mut player_query: Query<&mut Player>,
+    mut npc_query: Query<&mut NpcMover>,
     speech_query: Query<Entity, With<NpcText>>,
-    keyboard: Res<Input<KeyCode>>,
+    actions: Res<InputActions>,
 ) {
     let mut player = player_query.single_mut();
-    if keyboard.any_just_pressed([KeyCode::E, KeyCode::Space]) {
+    if actions.any_just_pressed([InputAction::Confirm, InputAction::Cancel]) {
         for ent in speech_query.iter() {
             player.active = true;
             commands.entity(ent).despawn_recursive();
+            for mut mover in npc_query.iter_mut() {
+                mover.talking = false;
+            }
         }
     }
 }
 
-fn spawn_textbox(
+pub fn in_interact_range(a: Vec3, b: Vec3) -> bool {
+    Vec2::distance(a.truncate(), b.truncate()) < TILE_SIZE * 1.5
+}
+
+pub fn spawn_textbox(
     commands: &mut Commands,
     ascii: &AsciiSheet,
-    indices: &NineSliceIndices,
-    translation: Vec2,
+    transform: Transform,
     text: &str,
 ) -> Entity {
     let width = text.len() as f32 + 2.0;
-    let text_nine_slice = spawn_nine_slice(commands, ascii, indices, width, 3.0);
+    let text_nine_slice = spawn_nine_slice(commands, width, 3.0);
     let background = spawn_ascii_sprite(
         commands,
         ascii,
@@ -60,14 +276,11 @@ fn spawn_textbox(
         Vec3::new(width, 3.0, 1.0),
     );
     let x_offset = (-width / 2.0 + 1.5) * TILE_SIZE;
-    let text = spawn_ascii_text(commands, ascii, text, Vec3::new(x_offset, 0.0, 0.0));
+    let text = spawn_ascii_text(commands, text, Vec3::new(x_offset, 0.0, 0.0));
 
     commands
         .spawn()
-        .insert(Transform {
-            translation: translation.extend(900.0),
-            ..Default::default()
-        })
+        .insert(transform)
         .insert(GlobalTransform::default())
         .insert(Name::new("Npc Text"))
         .insert(NpcText)
@@ -77,37 +290,45 @@ fn spawn_textbox(
         .id()
 }
 
-fn npc_speech(
+fn healer_line(time: TimeOfDay) -> &'static str {
+    match time {
+        TimeOfDay::Morning => "Good morning! Let me heal you.",
+        TimeOfDay::Day => "You seem weak, let me heal you!",
+        TimeOfDay::Evening => "Getting late, let me heal you.",
+        TimeOfDay::Night => "Up at this hour? Let me heal you.",
+    }
+}
+
+pub fn npc_speech(
     mut commands: Commands,
     mut player_query: Query<(&mut Player, &mut CombatStats, &Transform)>,
-    camera_query: Query<&Transform, With<Camera2d>>,
-    npc_query: Query<(&Npc, &Transform)>,
-    keyboard: Res<Input<KeyCode>>,
+    camera: Res<CameraController>,
+    clock: Res<GameClock>,
+    mut npc_query: Query<(&Npc, &Transform, &mut NpcMover, &mut NpcGraphics)>,
+    actions: Res<InputActions>,
     ascii: Res<AsciiSheet>,
-    indices: Res<NineSliceIndices>,
 ) {
     let (mut player, mut stats, transform) = player_query.single_mut();
-    let camera_transform = camera_query.single();
     if !player.active {
         return;
     }
 
-    if keyboard.just_pressed(KeyCode::E) {
-        for (_npc, npc_transform) in npc_query.iter() {
-            if Vec2::distance(
-                npc_transform.translation.truncate(),
-                transform.translation.truncate(),
-            ) < TILE_SIZE * 1.5
-            {
+    if actions.just_pressed(InputAction::Confirm) {
+        for (_npc, npc_transform, mut mover, mut graphics) in npc_query.iter_mut() {
+            if in_interact_range(npc_transform.translation, transform.translation) {
                 player.active = false;
+                mover.talking = true;
+                graphics.facing = facing_towards(
+                    npc_transform.translation.truncate(),
+                    transform.translation.truncate(),
+                );
                 stats.health = stats.max_health;
 
                 spawn_textbox(
                     &mut commands,
                     &ascii,
-                    &indices,
-                    Vec2::new(0.0, 1.0 - 1.5 * TILE_SIZE) + camera_transform.translation.truncate(),
-                    "You seem weak, let me heal you!",
+                    camera.screen_transform(Vec2::new(0.0, 1.0 - 1.5 * TILE_SIZE), 960.0),
+                    healer_line(clock.time_of_day()),
                 );
             }
         }
@@ -0,0 +1,138 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::collision::CollisionMap;
+
+const NEIGHBOURS: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+fn distance(a: (i32, i32), b: (i32, i32)) -> i32 {
+    (a.0 - b.0).abs() + (a.1 - b.1).abs()
+}
+
+//4-way A* over the walkable tiles, the path excludes the start and ends on the goal.
+//The start tile may be solid since whoever is pathing usually stands on it
+pub fn find_path(
+    map: &CollisionMap,
+    start: (i32, i32),
+    goal: (i32, i32),
+) -> Option<Vec<(i32, i32)>> {
+    if start == goal {
+        return Some(Vec::new());
+    }
+    if map.is_solid(goal.0, goal.1) {
+        return None;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<(i32, i32), (i32, i32)> = HashMap::new();
+    let mut cost: HashMap<(i32, i32), i32> = HashMap::new();
+    open.push(Reverse((distance(start, goal), 0, start)));
+    cost.insert(start, 0);
+
+    while let Some(Reverse((_, steps, tile))) = open.pop() {
+        if tile == goal {
+            let mut path = vec![goal];
+            let mut current = goal;
+            while let Some(previous) = came_from.get(&current) {
+                if *previous == start {
+                    break;
+                }
+                path.push(*previous);
+                current = *previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        //Stale entry, a shorter way here was already expanded
+        if steps > cost[&tile] {
+            continue;
+        }
+
+        for (dx, dy) in NEIGHBOURS {
+            let next = (tile.0 + dx, tile.1 + dy);
+            if map.is_solid(next.0, next.1) {
+                continue;
+            }
+            let next_steps = steps + 1;
+            if cost.get(&next).is_none_or(|known| next_steps < *known) {
+                cost.insert(next, next_steps);
+                came_from.insert(next, tile);
+                open.push(Reverse((
+                    next_steps + distance(next, goal),
+                    next_steps,
+                    next,
+                )));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::TileFlags;
+
+    //'#' is a wall, anything else is walkable
+    fn grid(rows: &[&str]) -> CollisionMap {
+        let mut map = CollisionMap::new(rows[0].len(), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, glyph) in row.chars().enumerate() {
+                let solid = glyph == '#';
+                map.set(
+                    x as i32,
+                    y as i32,
+                    TileFlags {
+                        solid,
+                        encounter_zone: None,
+                    },
+                );
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn straight_path() {
+        let map = grid(&["....."]);
+        let path = find_path(&map, (0, 0), (4, 0)).unwrap();
+        assert_eq!(path, vec![(1, 0), (2, 0), (3, 0), (4, 0)]);
+    }
+
+    #[test]
+    fn routes_around_walls() {
+        let map = grid(&[".....", ".###.", "..#.."]);
+        let path = find_path(&map, (1, 2), (3, 2)).unwrap();
+        assert_eq!(path.len(), 10);
+        assert_eq!(path.last(), Some(&(3, 2)));
+        let mut previous = (1, 2);
+        for tile in path {
+            assert!(!map.is_solid(tile.0, tile.1));
+            assert_eq!(distance(previous, tile), 1);
+            previous = tile;
+        }
+    }
+
+    #[test]
+    fn unreachable_goal() {
+        let map = grid(&["..#..", "..#.."]);
+        assert_eq!(find_path(&map, (0, 0), (4, 1)), None);
+        //Walls and tiles outside of the map can't be walked to either
+        assert_eq!(find_path(&map, (0, 0), (2, 0)), None);
+        assert_eq!(find_path(&map, (0, 0), (-1, 0)), None);
+    }
+
+    #[test]
+    fn start_is_goal() {
+        let map = grid(&["..", ".."]);
+        assert_eq!(find_path(&map, (1, 1), (1, 1)), Some(Vec::new()));
+    }
+
+    #[test]
+    fn starts_on_a_solid_tile() {
+        let map = grid(&["#.."]);
+        assert_eq!(find_path(&map, (0, 0), (2, 0)), Some(vec![(1, 0), (2, 0)]));
+    }
+}
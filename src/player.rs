@@ -1,25 +1,52 @@
 use crate::{
     ascii::{spawn_ascii_sprite, AsciiSheet},
+    camera::ScreenShake,
+    clock::GameClock,
     combat::CombatStats,
+    encounter::{ActiveEncounter, EncounterSettings, EncounterZones},
     fadeout::create_fadeout,
     graphics::{CharacterSheet, FacingDirection, FrameAnimation, PlayerGraphics},
-    tilemap::{EncounterSpawner, TileCollider},
+    input::{InputAction, InputActions},
+    tilemap::{tile_to_world, world_to_tile},
     GameState, MainCamera, TILE_SIZE,
 };
 use bevy::{
     prelude::*,
-    render::camera::Camera2d,
-    sprite::{collide_aabb::collide, SpriteSheetBundle, TextureAtlasSprite},
+    sprite::{SpriteSheetBundle, TextureAtlasSprite},
 };
+use bevy_2d_rpg::collision::CollisionMap;
 use bevy_inspector_egui::Inspectable;
 use rand::prelude::*;
 
 pub struct PlayerPlugin;
 
+//How long a direction key has to be held before a turning player starts walking
+const TURN_DELAY: f32 = 0.1;
+//Half of the player's hitbox, in tiles
+pub const PLAYER_HALF_SIZE: f32 = 0.45;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MovementMode {
+    Free,
+    Grid,
+}
+
+#[derive(Component)]
+pub struct GridMover {
+    start: Vec3,
+    target: Option<Vec3>,
+    progress: f32,
+    turning: bool,
+    turn_timer: Timer,
+}
+
 #[derive(Component, Default, Reflect)]
 #[reflect(Component)]
 pub struct EncounterTracker {
-    timer: Timer,
+    //Tiles walked inside encounter zones since the last fight
+    distance: f32,
+    //Tiles left to walk before fights can happen again
+    grace: f32,
 }
 
 #[derive(Component, Inspectable)]
@@ -27,6 +54,9 @@ pub struct Player {
     speed: f32,
     pub active: bool,
     just_moved: bool,
+    just_stepped: bool,
+    //Tiles travelled this frame, used by the encounter meter
+    distance_moved: f32,
     pub exp: usize,
 }
 
@@ -48,15 +78,23 @@ impl Player {
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_resume(GameState::Overworld).with_system(show_player))
+        app.insert_resource(MovementMode::Free)
+            .add_system_set(SystemSet::on_resume(GameState::Overworld).with_system(show_player))
             .add_system_set(SystemSet::on_pause(GameState::Overworld).with_system(hide_player))
             .add_system_set(
                 SystemSet::on_update(GameState::Overworld)
-                    .with_system(player_encounter_checking.after(player_movement))
-                    .with_system(camera_follow.after(player_movement))
-                    .with_system(player_movement),
+                    .with_system(
+                        player_encounter_checking
+                            .after(player_movement)
+                            .after(grid_player_movement),
+                    )
+                    .with_system(player_movement)
+                    .with_system(grid_player_movement),
             )
-            .add_system_set(SystemSet::on_enter(GameState::Overworld).with_system(spawn_player));
+            .add_system_set(SystemSet::on_enter(GameState::Overworld).with_system(spawn_player))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Combat).with_system(start_encounter_grace),
+            );
     }
 }
 
@@ -95,53 +133,46 @@ fn show_player(
     }
 }
 
-fn camera_follow(
-    player_query: Query<&Transform, With<Player>>,
-    mut camera_query: Query<&mut Transform, (Without<Player>, With<Camera2d>)>,
-) {
-    let player_transform = player_query.single();
-    let mut camera_transform = camera_query.single_mut();
-
-    camera_transform.translation.x = player_transform.translation.x;
-    camera_transform.translation.y = player_transform.translation.y;
-}
-
 fn player_movement(
     mut player_query: Query<(&mut Player, &mut Transform, &mut PlayerGraphics)>,
-    wall_query: Query<&Transform, (With<TileCollider>, Without<Player>)>,
-    keyboard: Res<Input<KeyCode>>,
+    collision_map: Res<CollisionMap>,
+    actions: Res<InputActions>,
+    movement_mode: Res<MovementMode>,
     time: Res<Time>,
 ) {
+    if *movement_mode != MovementMode::Free {
+        return;
+    }
+
     let (mut player, mut transform, mut graphics) = player_query.single_mut();
     player.just_moved = false;
+    player.distance_moved = 0.0;
 
     if !player.active {
         return;
     }
 
     let mut y_delta = 0.0;
-    if keyboard.pressed(KeyCode::W) {
+    if actions.pressed(InputAction::MoveUp) {
         y_delta += player.speed * TILE_SIZE * time.delta_seconds();
     }
-    if keyboard.pressed(KeyCode::S) {
+    if actions.pressed(InputAction::MoveDown) {
         y_delta -= player.speed * TILE_SIZE * time.delta_seconds();
     }
 
     let mut x_delta = 0.0;
-    if keyboard.pressed(KeyCode::D) {
+    if actions.pressed(InputAction::MoveRight) {
         x_delta += player.speed * TILE_SIZE * time.delta_seconds();
     }
-    if keyboard.pressed(KeyCode::A) {
+    if actions.pressed(InputAction::MoveLeft) {
         x_delta -= player.speed * TILE_SIZE * time.delta_seconds();
     }
 
     let target = transform.translation + Vec3::new(0.0, y_delta, 0.0);
-    if !wall_query
-        .iter()
-        .any(|&transform| wall_collision_check(target, transform.translation))
-    {
+    if !collision_map.overlaps_solid(world_to_tile_space(target), PLAYER_HALF_SIZE) {
         if y_delta != 0.0 {
             player.just_moved = true;
+            player.distance_moved += f32::abs(y_delta) / TILE_SIZE;
             if y_delta > 0.0 {
                 graphics.facing = FacingDirection::Up;
             } else {
@@ -152,12 +183,10 @@ fn player_movement(
     }
 
     let target = transform.translation + Vec3::new(x_delta, 0.0, 0.0);
-    if !wall_query
-        .iter()
-        .any(|&transform| wall_collision_check(target, transform.translation))
-    {
+    if !collision_map.overlaps_solid(world_to_tile_space(target), PLAYER_HALF_SIZE) {
         if x_delta != 0.0 {
             player.just_moved = true;
+            player.distance_moved += f32::abs(x_delta) / TILE_SIZE;
             if x_delta > 0.0 {
                 graphics.facing = FacingDirection::Right;
             } else {
@@ -168,40 +197,161 @@ fn player_movement(
     }
 }
 
-fn wall_collision_check(target_player_pos: Vec3, wall_transform: Vec3) -> bool {
-    let collision = collide(
-        target_player_pos,
-        Vec2::splat(TILE_SIZE * 0.9),
-        wall_transform,
-        Vec2::splat(TILE_SIZE),
-    );
-    return collision.is_some();
+fn grid_player_movement(
+    mut player_query: Query<(
+        &mut Player,
+        &mut Transform,
+        &mut PlayerGraphics,
+        &mut GridMover,
+    )>,
+    collision_map: Res<CollisionMap>,
+    actions: Res<InputActions>,
+    movement_mode: Res<MovementMode>,
+    time: Res<Time>,
+) {
+    if *movement_mode != MovementMode::Grid {
+        return;
+    }
+
+    let (mut player, mut transform, mut graphics, mut mover) = player_query.single_mut();
+    player.just_moved = false;
+    player.just_stepped = false;
+    player.distance_moved = 0.0;
+
+    //Finish the current step before accepting new input
+    if let Some(target) = mover.target {
+        player.just_moved = true;
+        mover.progress += player.speed * time.delta_seconds();
+        if mover.progress >= 1.0 {
+            transform.translation = target;
+            mover.target = None;
+            mover.progress = 0.0;
+            player.just_stepped = true;
+            player.distance_moved = 1.0;
+        } else {
+            transform.translation = mover.start.lerp(target, mover.progress);
+        }
+        return;
+    }
+
+    if !player.active {
+        return;
+    }
+
+    let (direction, offset) = if actions.pressed(InputAction::MoveUp) {
+        (FacingDirection::Up, (0, -1))
+    } else if actions.pressed(InputAction::MoveDown) {
+        (FacingDirection::Down, (0, 1))
+    } else if actions.pressed(InputAction::MoveLeft) {
+        (FacingDirection::Left, (-1, 0))
+    } else if actions.pressed(InputAction::MoveRight) {
+        (FacingDirection::Right, (1, 0))
+    } else {
+        mover.turning = false;
+        return;
+    };
+
+    //A tap in a new direction only turns the player
+    if graphics.facing != direction {
+        graphics.facing = direction;
+        mover.turning = true;
+        mover.turn_timer.reset();
+        return;
+    }
+    if mover.turning {
+        mover.turn_timer.tick(time.delta());
+        if !mover.turn_timer.finished() {
+            return;
+        }
+        mover.turning = false;
+    }
+
+    let (x, y) = world_to_tile(transform.translation);
+    let target_tile = (x + offset.0, y + offset.1);
+    if collision_map.is_solid(target_tile.0, target_tile.1) {
+        return;
+    }
+
+    mover.start = tile_to_world((x, y), transform.translation.z);
+    mover.target = Some(tile_to_world(target_tile, transform.translation.z));
+    mover.progress = 0.0;
+}
+
+pub fn world_to_tile_space(translation: Vec3) -> Vec2 {
+    Vec2::new(translation.x / TILE_SIZE, -translation.y / TILE_SIZE)
 }
 
 fn player_encounter_checking(
     mut commands: Commands,
     mut player_query: Query<(&mut Player, &mut EncounterTracker, &Transform)>,
-    encounter_query: Query<&Transform, (With<EncounterSpawner>, Without<Player>)>,
-    mut state: ResMut<State<GameState>>,
+    collision_map: Res<CollisionMap>,
+    zones: Res<EncounterZones>,
+    settings: Res<EncounterSettings>,
+    clock: Res<GameClock>,
+    mut shake: ResMut<ScreenShake>,
     ascii: Res<AsciiSheet>,
-    mut time: Res<Time>,
 ) {
     let (mut player, mut encounter_tracker, player_transform) = player_query.single_mut();
     let player_translation = player_transform.translation;
-    if player.just_moved
-        && encounter_query
-            .iter()
-            .any(|&transform| wall_collision_check(player_translation, transform.translation))
+
+    //Grid movement only reports distance once a step is completed
+    let moved = player.distance_moved;
+    if moved <= 0.0 {
+        return;
+    }
+
+    let zone_id = match collision_map
+        .overlapped_encounter_zone(world_to_tile_space(player_translation), PLAYER_HALF_SIZE)
     {
-        encounter_tracker.timer.tick(time.delta());
-        if encounter_tracker.timer.just_finished() {
+        Some(zone_id) => zone_id,
+        None => return,
+    };
+    let zone = match zones.get(zone_id) {
+        Some(zone) => zone,
+        None => return,
+    };
+
+    if encounter_tracker.grace > 0.0 {
+        encounter_tracker.grace -= moved;
+        return;
+    }
+
+    //Roll once for every whole tile walked, so frame rate doesn't change the odds
+    let before = encounter_tracker.distance;
+    encounter_tracker.distance += moved;
+    let tiles_crossed = encounter_tracker.distance.floor() - before.floor();
+    for _ in 0..tiles_crossed as usize {
+        let chance = settings.danger(encounter_tracker.distance) * zone.rate;
+        if thread_rng().gen::<f32>() < chance {
+            //Zones can be quiet at some times of day
+            let enemy = match zone.pick_enemy(clock.time_of_day()) {
+                Some(enemy) => enemy,
+                None => continue,
+            };
             player.active = false;
-            encounter_tracker.timer = Timer::from_seconds(thread_rng().gen_range(1.0..=6.0), true);
+            encounter_tracker.distance = 0.0;
+            shake.add_trauma(0.3);
+            commands.insert_resource(ActiveEncounter {
+                zone: Some(zone_id),
+                enemy,
+                level: zone.pick_level(),
+            });
             create_fadeout(&mut commands, Some(GameState::Combat), &ascii);
+            return;
         }
     }
 }
 
+fn start_encounter_grace(
+    mut tracker_query: Query<&mut EncounterTracker>,
+    settings: Res<EncounterSettings>,
+) {
+    for mut tracker in tracker_query.iter_mut() {
+        tracker.distance = 0.0;
+        tracker.grace = settings.grace_steps;
+    }
+}
+
 fn spawn_player(mut commands: Commands, characters: Res<CharacterSheet>) {
     // /* Creates player ascii sprite */
     // let player = spawn_ascii_sprite(
@@ -220,7 +370,8 @@ fn spawn_player(mut commands: Commands, characters: Res<CharacterSheet>) {
                 custom_size: Some(Vec2::splat(TILE_SIZE)),
                 ..default()
             },
-            transform: Transform::from_xyz(2.0 * TILE_SIZE, -2.0 * TILE_SIZE, 900.0),
+            //Moved onto the map's spawn once the map has loaded
+            transform: Transform::from_xyz(0.0, 0.0, 900.0),
             texture_atlas: characters.handle.clone(),
             ..default()
         })
@@ -237,17 +388,24 @@ fn spawn_player(mut commands: Commands, characters: Res<CharacterSheet>) {
             speed: 3.0,
             active: true,
             just_moved: false,
+            just_stepped: false,
+            distance_moved: 0.0,
             exp: 0,
         })
+        .insert(GridMover {
+            start: Vec3::ZERO,
+            target: None,
+            progress: 0.0,
+            turning: false,
+            turn_timer: Timer::from_seconds(TURN_DELAY, false),
+        })
         .insert(CombatStats {
             health: 10,
             max_health: 10,
             attack: 2,
             defense: 1,
         })
-        .insert(EncounterTracker {
-            timer: Timer::from_seconds(thread_rng().gen_range(0.0..=6.0), true),
-        });
+        .insert(EncounterTracker::default());
 
     // /* Creates background ascii OLD */
     // let background = spawn_ascii_sprite(
@@ -0,0 +1,218 @@
+use bevy::{
+    math::{Vec2, Vec3},
+    render::{
+        mesh::{Indices, Mesh, VertexAttributeValues},
+        render_resource::PrimitiveTopology,
+    },
+    sprite::TextureAtlas,
+};
+
+//Map tiles are batched in square chunks of this many tiles
+pub const CHUNK_SIZE: i32 = 16;
+
+//One textured rectangle of a batched mesh, in the local space of its entity
+#[derive(Clone, Copy, Debug)]
+pub struct Quad {
+    pub center: Vec3,
+    pub size: Vec2,
+    //Index into the texture atlas
+    pub index: usize,
+}
+
+pub fn chunk_of(tile: (i32, i32)) -> (i32, i32) {
+    (tile.0.div_euclid(CHUNK_SIZE), tile.1.div_euclid(CHUNK_SIZE))
+}
+
+//Every tile of a chunk, clipped to the map
+pub fn chunk_tiles(
+    chunk: (i32, i32),
+    width: usize,
+    height: usize,
+) -> impl Iterator<Item = (i32, i32)> {
+    let (left, top) = (chunk.0 * CHUNK_SIZE, chunk.1 * CHUNK_SIZE);
+    let right = (left + CHUNK_SIZE).min(width as i32);
+    let bottom = (top + CHUNK_SIZE).min(height as i32);
+    (top..bottom).flat_map(move |y| (left..right).map(move |x| (x, y)))
+}
+
+//All chunks covering a map of the given size
+pub fn map_chunks(width: usize, height: usize) -> impl Iterator<Item = (i32, i32)> {
+    let columns = (width as i32 + CHUNK_SIZE - 1) / CHUNK_SIZE;
+    let rows = (height as i32 + CHUNK_SIZE - 1) / CHUNK_SIZE;
+    (0..rows).flat_map(move |y| (0..columns).map(move |x| (x, y)))
+}
+
+//Corners of an atlas rect in the order quads_mesh lays out the vertices of a quad
+fn quad_uvs(atlas: &TextureAtlas, index: usize) -> [[f32; 2]; 4] {
+    let rect = match atlas.textures.get(index) {
+        Some(rect) => rect,
+        None => return [[0.0, 0.0]; 4],
+    };
+    let min = rect.min / atlas.size;
+    let max = rect.max / atlas.size;
+    //Image rows go downwards, so the top of the quad samples the top of the rect
+    [
+        [min.x, max.y],
+        [max.x, max.y],
+        [max.x, min.y],
+        [min.x, min.y],
+    ]
+}
+
+//Builds one mesh drawing every quad with the atlas' texture, so they all go out in one draw
+pub fn quads_mesh(atlas: &TextureAtlas, quads: &[Quad]) -> Mesh {
+    let mut positions = Vec::with_capacity(quads.len() * 4);
+    let mut normals = Vec::with_capacity(quads.len() * 4);
+    let mut uvs = Vec::with_capacity(quads.len() * 4);
+    let mut indices = Vec::with_capacity(quads.len() * 6);
+
+    for quad in quads {
+        //Quads missing from the atlas are left empty, so the ones after keep their place
+        let half = match atlas.textures.get(quad.index) {
+            Some(_) => quad.size / 2.0,
+            None => Vec2::ZERO,
+        };
+        let (x, y, z) = (quad.center.x, quad.center.y, quad.center.z);
+
+        let start = positions.len() as u32;
+        positions.extend([
+            [x - half.x, y - half.y, z],
+            [x + half.x, y - half.y, z],
+            [x + half.x, y + half.y, z],
+            [x - half.x, y + half.y, z],
+        ]);
+        normals.extend([[0.0, 0.0, 1.0]; 4]);
+        uvs.extend(quad_uvs(atlas, quad.index));
+        indices.extend([start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+//Points the nth quad of a quads_mesh mesh at another atlas index, without rebuilding the mesh
+pub fn set_quad_index(mesh: &mut Mesh, atlas: &TextureAtlas, quad: usize, index: usize) {
+    if let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
+        if let Some(corners) = uvs.get_mut(quad * 4..quad * 4 + 4) {
+            corners.copy_from_slice(&quad_uvs(atlas, index));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::Handle;
+
+    //Two 16x16 tiles side by side
+    fn atlas() -> TextureAtlas {
+        TextureAtlas::from_grid(Handle::default(), Vec2::splat(16.0), 2, 1)
+    }
+
+    fn uvs(mesh: &Mesh) -> &[[f32; 2]] {
+        match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(uvs)) => uvs,
+            other => panic!("unexpected uvs {:?}", other),
+        }
+    }
+
+    fn positions(mesh: &Mesh) -> &[[f32; 3]] {
+        match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions,
+            other => panic!("unexpected positions {:?}", other),
+        }
+    }
+
+    #[test]
+    fn chunks_round_towards_negative() {
+        assert_eq!(chunk_of((0, 15)), (0, 0));
+        assert_eq!(chunk_of((16, 31)), (1, 1));
+        assert_eq!(chunk_of((-1, -16)), (-1, -1));
+        assert_eq!(chunk_of((-17, 0)), (-2, 0));
+    }
+
+    #[test]
+    fn chunk_tiles_are_clipped_to_the_map() {
+        let tiles: Vec<_> = chunk_tiles((1, 1), 20, 18).collect();
+        assert_eq!(tiles.len(), 4 * 2);
+        assert_eq!(tiles.first(), Some(&(16, 16)));
+        assert_eq!(tiles.last(), Some(&(19, 17)));
+        assert_eq!(chunk_tiles((0, 0), 40, 40).count(), 16 * 16);
+        assert_eq!(chunk_tiles((2, 0), 20, 18).count(), 0);
+    }
+
+    #[test]
+    fn map_chunks_cover_partial_chunks() {
+        assert_eq!(map_chunks(16, 16).count(), 1);
+        assert_eq!(
+            map_chunks(17, 32).collect::<Vec<_>>(),
+            vec![(0, 0), (1, 0), (0, 1), (1, 1)]
+        );
+        assert_eq!(map_chunks(0, 0).count(), 0);
+    }
+
+    #[test]
+    fn quads_are_laid_out_counter_clockwise_from_the_bottom_left() {
+        let quads = [
+            Quad {
+                center: Vec3::new(1.0, 2.0, 3.0),
+                size: Vec2::splat(2.0),
+                index: 1,
+            },
+            Quad {
+                center: Vec3::new(5.0, 5.0, 0.0),
+                size: Vec2::splat(2.0),
+                index: 7,
+            },
+        ];
+        let mesh = quads_mesh(&atlas(), &quads);
+        assert_eq!(
+            &positions(&mesh)[..4],
+            &[
+                [0.0, 1.0, 3.0],
+                [2.0, 1.0, 3.0],
+                [2.0, 3.0, 3.0],
+                [0.0, 3.0, 3.0]
+            ]
+        );
+        assert_eq!(
+            &uvs(&mesh)[..4],
+            &[[0.5, 1.0], [1.0, 1.0], [1.0, 0.0], [0.5, 0.0]]
+        );
+        //Missing from the atlas, so it collapses to a point but keeps its vertices
+        assert_eq!(&positions(&mesh)[4..], &[[5.0, 5.0, 0.0]; 4]);
+        match mesh.indices() {
+            Some(Indices::U32(indices)) => {
+                assert_eq!(indices, &[0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7])
+            }
+            other => panic!("unexpected indices {:?}", other),
+        }
+    }
+
+    #[test]
+    fn set_quad_index_only_touches_that_quad() {
+        let atlas = atlas();
+        let quad = Quad {
+            center: Vec3::ZERO,
+            size: Vec2::ONE,
+            index: 1,
+        };
+        let mut mesh = quads_mesh(&atlas, &[quad, quad]);
+        set_quad_index(&mut mesh, &atlas, 1, 0);
+        assert_eq!(
+            &uvs(&mesh)[..4],
+            &[[0.5, 1.0], [1.0, 1.0], [1.0, 0.0], [0.5, 0.0]]
+        );
+        assert_eq!(
+            &uvs(&mesh)[4..],
+            &[[0.0, 1.0], [0.5, 1.0], [0.5, 0.0], [0.0, 0.0]]
+        );
+        //Quads past the end are ignored
+        set_quad_index(&mut mesh, &atlas, 2, 0);
+        assert_eq!(uvs(&mesh).len(), 8);
+    }
+}
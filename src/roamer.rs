@@ -0,0 +1,303 @@
+use bevy::prelude::*;
+use bevy_2d_rpg::collision::CollisionMap;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ascii::AsciiSheet,
+    camera::ScreenShake,
+    combat::{CombatState, EnemyType},
+    encounter::ActiveEncounter,
+    fadeout::create_fadeout,
+    graphics::{spawn_enemy_sprite, CharacterSheet},
+    player::{world_to_tile_space, Player},
+    tilemap::{tile_to_world, world_to_tile, CurrentMap, Map},
+    GameState, TILE_SIZE,
+};
+
+pub struct RoamerPlugin;
+
+//Half of a roamer's hitbox against walls, in tiles
+const ROAMER_HALF_SIZE: f32 = 0.4;
+//How close a roamer has to get to the player to start a fight, in tiles
+const CONTACT_DISTANCE: f32 = 0.8;
+
+//Enemies walking around the map, listed in the map's .ron file
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RoamerSpawn {
+    pub enemy: EnemyType,
+    pub position: (i32, i32),
+    pub level: usize,
+}
+
+#[derive(Component)]
+pub struct Roamer {
+    //Index into the roamers of the current map
+    spawn: usize,
+    enemy: EnemyType,
+    level: usize,
+    home: Vec3,
+    direction: Vec2,
+    wander_timer: Timer,
+    //Seconds left where the roamer leaves the player alone
+    stunned: f32,
+}
+
+pub struct RoamerSettings {
+    //Tiles at which a roamer notices the player
+    pub chase_range: f32,
+    //Tiles per second
+    pub chase_speed: f32,
+    pub wander_speed: f32,
+    //Tiles a wandering roamer may stray from its spawn
+    pub wander_radius: f32,
+    //Seconds before a defeated roamer comes back
+    pub respawn_delay: f32,
+    //Seconds a roamer ignores the player after they ran from it
+    pub flee_stun: f32,
+}
+
+impl Default for RoamerSettings {
+    fn default() -> Self {
+        RoamerSettings {
+            chase_range: 4.0,
+            chase_speed: 2.0,
+            wander_speed: 1.0,
+            wander_radius: 3.0,
+            respawn_delay: 60.0,
+            flee_stun: 3.0,
+        }
+    }
+}
+
+//The roamer that started the current fight
+struct EngagedRoamer(Entity);
+
+//Defeated roamers waiting to come back, by map name and spawn index
+#[derive(Default)]
+struct RoamerRespawns(Vec<(String, usize, Timer)>);
+
+impl Plugin for RoamerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RoamerSettings>()
+            .init_resource::<RoamerRespawns>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Overworld)
+                    .with_system(spawn_map_roamers)
+                    .with_system(respawn_roamers)
+                    .with_system(roamer_movement)
+                    .with_system(roamer_contact.after(roamer_movement)),
+            )
+            .add_system_set(
+                SystemSet::on_enter(CombatState::Reward).with_system(defeat_engaged_roamer),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Combat).with_system(release_engaged_roamer),
+            );
+    }
+}
+
+fn spawn_roamer(
+    commands: &mut Commands,
+    characters: &CharacterSheet,
+    map: Entity,
+    index: usize,
+    spawn: &RoamerSpawn,
+) {
+    let home = tile_to_world(spawn.position, 800.0);
+    let roamer = spawn_enemy_sprite(commands, characters, home, TILE_SIZE, spawn.enemy);
+    commands
+        .entity(roamer)
+        .insert(Name::new(format!("Roamer {:?}", spawn.enemy)))
+        .insert(Roamer {
+            spawn: index,
+            enemy: spawn.enemy,
+            level: spawn.level,
+            home,
+            direction: Vec2::ZERO,
+            wander_timer: Timer::from_seconds(1.5, true),
+            stunned: 0.0,
+        });
+    //Parented to the map so they are hidden and cleared along with it
+    commands.entity(map).add_child(roamer);
+}
+
+fn spawn_map_roamers(
+    mut commands: Commands,
+    current_map: Res<CurrentMap>,
+    map_query: Query<Entity, With<Map>>,
+    respawns: Res<RoamerRespawns>,
+    characters: Res<CharacterSheet>,
+) {
+    if !current_map.is_changed() {
+        return;
+    }
+    let map = match map_query.get_single() {
+        Ok(map) => map,
+        Err(_) => return,
+    };
+    for (index, spawn) in current_map.roamers.iter().enumerate() {
+        let waiting = respawns
+            .0
+            .iter()
+            .any(|(name, pending, _)| *name == current_map.name && *pending == index);
+        if !waiting {
+            spawn_roamer(&mut commands, &characters, map, index, spawn);
+        }
+    }
+}
+
+fn respawn_roamers(
+    mut commands: Commands,
+    mut respawns: ResMut<RoamerRespawns>,
+    current_map: Res<CurrentMap>,
+    map_query: Query<Entity, With<Map>>,
+    characters: Res<CharacterSheet>,
+    time: Res<Time>,
+) {
+    for (_, _, timer) in respawns.0.iter_mut() {
+        timer.tick(time.delta());
+    }
+
+    //Roamers of other maps simply spawn with their map once they are off the list
+    if let Ok(map) = map_query.get_single() {
+        for (name, index, timer) in respawns.0.iter() {
+            if timer.finished() && *name == current_map.name {
+                if let Some(spawn) = current_map.roamers.get(*index) {
+                    spawn_roamer(&mut commands, &characters, map, *index, spawn);
+                }
+            }
+        }
+    }
+    respawns.0.retain(|(_, _, timer)| !timer.finished());
+}
+
+fn roamer_movement(
+    mut roamer_query: Query<(&mut Roamer, &mut Transform, &mut TextureAtlasSprite)>,
+    player_query: Query<(&Player, &Transform), Without<Roamer>>,
+    collision_map: Res<CollisionMap>,
+    settings: Res<RoamerSettings>,
+    time: Res<Time>,
+) {
+    let (player, player_transform) = player_query.single();
+    if !player.active {
+        return;
+    }
+
+    let mut rng = thread_rng();
+    for (mut roamer, mut transform, mut sprite) in roamer_query.iter_mut() {
+        roamer.stunned = (roamer.stunned - time.delta_seconds()).max(0.0);
+
+        let to_player = (player_transform.translation - transform.translation).truncate();
+        let chasing =
+            roamer.stunned <= 0.0 && to_player.length() < settings.chase_range * TILE_SIZE;
+
+        let (direction, speed) = if chasing {
+            (to_player.normalize_or_zero(), settings.chase_speed)
+        } else {
+            roamer.wander_timer.tick(time.delta());
+            if roamer.wander_timer.just_finished() {
+                let to_home = (roamer.home - transform.translation).truncate();
+                roamer.direction = if to_home.length() > settings.wander_radius * TILE_SIZE {
+                    to_home.normalize_or_zero()
+                } else if rng.gen_bool(0.3) {
+                    Vec2::ZERO
+                } else {
+                    Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0))
+                        .normalize_or_zero()
+                };
+            }
+            (roamer.direction, settings.wander_speed)
+        };
+
+        let delta = direction * speed * TILE_SIZE * time.delta_seconds();
+        if delta.x != 0.0 {
+            sprite.flip_x = delta.x < 0.0;
+        }
+
+        //Slide along walls by trying each axis on its own
+        let target = transform.translation + Vec3::new(delta.x, 0.0, 0.0);
+        if !collision_map.overlaps_solid(world_to_tile_space(target), ROAMER_HALF_SIZE) {
+            transform.translation = target;
+        }
+        let target = transform.translation + Vec3::new(0.0, delta.y, 0.0);
+        if !collision_map.overlaps_solid(world_to_tile_space(target), ROAMER_HALF_SIZE) {
+            transform.translation = target;
+        }
+    }
+}
+
+fn roamer_contact(
+    mut commands: Commands,
+    roamer_query: Query<(Entity, &Roamer, &Transform)>,
+    mut player_query: Query<(&mut Player, &Transform), Without<Roamer>>,
+    collision_map: Res<CollisionMap>,
+    mut shake: ResMut<ScreenShake>,
+    ascii: Res<AsciiSheet>,
+) {
+    let (mut player, player_transform) = player_query.single_mut();
+    if !player.active {
+        return;
+    }
+
+    for (entity, roamer, transform) in roamer_query.iter() {
+        if roamer.stunned > 0.0 {
+            continue;
+        }
+        let distance = transform
+            .translation
+            .truncate()
+            .distance(player_transform.translation.truncate());
+        if distance < CONTACT_DISTANCE * TILE_SIZE {
+            let (x, y) = world_to_tile(transform.translation);
+            player.active = false;
+            shake.add_trauma(0.3);
+            commands.insert_resource(ActiveEncounter {
+                zone: collision_map.encounter_zone(x, y),
+                enemy: roamer.enemy,
+                level: roamer.level,
+            });
+            commands.insert_resource(EngagedRoamer(entity));
+            create_fadeout(&mut commands, Some(GameState::Combat), &ascii);
+            return;
+        }
+    }
+}
+
+fn defeat_engaged_roamer(
+    mut commands: Commands,
+    engaged: Option<Res<EngagedRoamer>>,
+    roamer_query: Query<&Roamer>,
+    mut respawns: ResMut<RoamerRespawns>,
+    current_map: Res<CurrentMap>,
+    settings: Res<RoamerSettings>,
+) {
+    let entity = match engaged {
+        Some(engaged) => engaged.0,
+        None => return,
+    };
+    if let Ok(roamer) = roamer_query.get(entity) {
+        respawns.0.push((
+            current_map.name.clone(),
+            roamer.spawn,
+            Timer::from_seconds(settings.respawn_delay, false),
+        ));
+    }
+    commands.entity(entity).despawn_recursive();
+    commands.remove_resource::<EngagedRoamer>();
+}
+
+//Only still engaged if the player ran, give them a head start
+fn release_engaged_roamer(
+    mut commands: Commands,
+    engaged: Option<Res<EngagedRoamer>>,
+    mut roamer_query: Query<&mut Roamer>,
+    settings: Res<RoamerSettings>,
+) {
+    if let Some(engaged) = engaged {
+        if let Ok(mut roamer) = roamer_query.get_mut(engaged.0) {
+            roamer.stunned = settings.flee_stun;
+        }
+        commands.remove_resource::<EngagedRoamer>();
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{clock::GameClock, tilemap::CurrentMap};
+
+pub struct SavePlugin;
+
+const SAVE_PATH: &str = "save.ron";
+
+//Things that have to stay the way the player left them, keyed by map name and tile
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct WorldFlags {
+    pub opened_chests: HashSet<(String, i32, i32)>,
+    pub switches_on: HashSet<(String, i32, i32)>,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Inventory {
+    pub items: BTreeMap<String, usize>,
+}
+
+//Tiles the player has seen on each map, shown on the minimap and through the fog of dark maps
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct ExploredMaps {
+    pub maps: BTreeMap<String, HashSet<(i32, i32)>>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SaveData {
+    #[serde(default)]
+    flags: WorldFlags,
+    #[serde(default)]
+    inventory: Inventory,
+    #[serde(default)]
+    clock: GameClock,
+    #[serde(default)]
+    explored: ExploredMaps,
+}
+
+impl Inventory {
+    pub fn add(&mut self, item: &str) {
+        *self.items.entry(item.to_string()).or_insert(0) += 1;
+    }
+}
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        let save = load_save();
+        app.insert_resource(save.flags)
+            .insert_resource(save.inventory)
+            .insert_resource(save.clock)
+            .insert_resource(save.explored)
+            .add_system_to_stage(CoreStage::Last, write_save);
+    }
+}
+
+fn load_save() -> SaveData {
+    let contents = match fs::read_to_string(SAVE_PATH) {
+        Ok(contents) => contents,
+        Err(_) => return SaveData::default(),
+    };
+    ron::from_str(&contents).unwrap_or_else(|err| {
+        warn!("Could not parse {}, starting fresh: {}", SAVE_PATH, err);
+        SaveData::default()
+    })
+}
+
+//The clock changes every frame, so it is only written once per in-game hour.
+//Exploration only counts as changed when new tiles are seen
+fn write_save(
+    flags: Res<WorldFlags>,
+    inventory: Res<Inventory>,
+    clock: Res<GameClock>,
+    explored: Res<ExploredMaps>,
+    current_map: Res<CurrentMap>,
+    mut saved_hour: Local<Option<(u32, u32)>>,
+) {
+    let flags_changed = flags.is_changed() && !flags.is_added();
+    let inventory_changed = inventory.is_changed() && !inventory.is_added();
+    let explored_changed = explored.is_changed() && !explored.is_added();
+    let map_changed = current_map.is_changed() && !current_map.is_added();
+    let hour = (clock.day, clock.hour as u32);
+    let hour_passed = saved_hour.is_some_and(|saved| saved != hour);
+    if saved_hour.is_none() {
+        *saved_hour = Some(hour);
+    }
+    if !flags_changed && !inventory_changed && !explored_changed && !map_changed && !hour_passed {
+        return;
+    }
+    *saved_hour = Some(hour);
+
+    let save = SaveData {
+        flags: flags.clone(),
+        inventory: inventory.clone(),
+        clock: clock.clone(),
+        explored: explored.clone(),
+    };
+    let contents = ron::ser::to_string_pretty(&save, ron::ser::PrettyConfig::default())
+        .expect("Save data should always serialize");
+    if let Err(err) = fs::write(SAVE_PATH, contents) {
+        warn!("Could not write {}: {}", SAVE_PATH, err);
+    }
+}
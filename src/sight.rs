@@ -0,0 +1,103 @@
+use std::collections::HashSet;
+
+//Tiles on a straight line between two tiles, both ends included
+fn line(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let step_x = if to.0 > from.0 { 1 } else { -1 };
+    let step_y = if to.1 > from.1 { 1 } else { -1 };
+    let mut error = dx + dy;
+    let mut tile = from;
+    let mut tiles = vec![from];
+    while tile != to {
+        let doubled = error * 2;
+        if doubled >= dy {
+            error += dy;
+            tile.0 += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            tile.1 += step_y;
+        }
+        tiles.push(tile);
+    }
+    tiles
+}
+
+//Tiles within the radius that nothing opaque stands in front of.
+//Walls themselves are seen, only what lies behind them is hidden
+pub fn visible_tiles(
+    center: (i32, i32),
+    radius: i32,
+    is_opaque: impl Fn((i32, i32)) -> bool,
+) -> HashSet<(i32, i32)> {
+    let mut visible = HashSet::new();
+    for y in -radius..=radius {
+        for x in -radius..=radius {
+            if x * x + y * y > radius * radius {
+                continue;
+            }
+            let target = (center.0 + x, center.1 + y);
+            let tiles = line(center, target);
+            let between = &tiles[1..tiles.len().saturating_sub(1).max(1)];
+            if between.iter().all(|tile| !is_opaque(*tile)) {
+                visible.insert(target);
+            }
+        }
+    }
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //'#' is opaque, anything else is see through
+    fn visible(rows: &[&str], center: (i32, i32), radius: i32) -> HashSet<(i32, i32)> {
+        visible_tiles(center, radius, |(x, y)| {
+            rows.get(y as usize)
+                .and_then(|row| row.chars().nth(x as usize))
+                == Some('#')
+        })
+    }
+
+    #[test]
+    fn line_includes_both_ends() {
+        assert_eq!(line((0, 0), (3, 0)), vec![(0, 0), (1, 0), (2, 0), (3, 0)]);
+        assert_eq!(line((2, 2), (0, 0)), vec![(2, 2), (1, 1), (0, 0)]);
+        assert_eq!(line((1, 1), (1, 1)), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn line_steps_one_tile_at_a_time() {
+        let tiles = line((0, 0), (5, 2));
+        assert_eq!(tiles.last(), Some(&(5, 2)));
+        for pair in tiles.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            assert!((a.0 - b.0).abs() <= 1 && (a.1 - b.1).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn center_is_always_visible() {
+        assert!(visible(&["#"], (0, 0), 0).contains(&(0, 0)));
+        assert!(visible(&["###", "###", "###"], (1, 1), 3).contains(&(1, 1)));
+    }
+
+    #[test]
+    fn walls_are_seen_but_hide_what_is_behind() {
+        let seen = visible(&[".....", "..#..", "....."], (2, 0), 3);
+        assert!(seen.contains(&(2, 1)));
+        assert!(!seen.contains(&(2, 2)));
+        assert!(seen.contains(&(0, 2)));
+    }
+
+    #[test]
+    fn radius_is_circular() {
+        let seen = visible(&[], (0, 0), 2);
+        assert!(seen.contains(&(2, 0)));
+        assert!(seen.contains(&(1, 1)));
+        assert!(!seen.contains(&(2, 2)));
+        assert!(!seen.contains(&(3, 0)));
+        assert_eq!(seen.len(), 13);
+    }
+}
@@ -0,0 +1,976 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::map::{Legend, LegendEntry, MapData, MapLayer, ObjectType};
+
+//Tiled stores flipping in the top bits of every gid
+const FLIP_FLAGS: u32 = 0xE000_0000;
+
+pub type Properties = HashMap<String, String>;
+
+//Maps saved by the Tiled editor, as JSON (.tmj) or XML (.tmx).
+//Only what the game understands is kept: csv tile data, tile and object layers and properties
+#[derive(Debug, Clone)]
+pub struct TiledMap {
+    pub width: usize,
+    pub height: usize,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tilesets: Vec<TiledTileset>,
+    pub layers: Vec<TiledLayer>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TiledTileset {
+    pub first_gid: u32,
+    pub name: String,
+    //Resolved against the map file, see normalize
+    pub image: PathBuf,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub columns: u32,
+    pub tile_count: u32,
+    pub spacing: u32,
+    //Keyed by the tile id inside the tileset
+    pub tile_properties: HashMap<u32, Properties>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TiledLayer {
+    Tiles {
+        name: String,
+        //Row major gids with the flip flags removed, 0 is an empty tile
+        data: Vec<u32>,
+        properties: Properties,
+    },
+    Objects {
+        name: String,
+        objects: Vec<TiledObject>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct TiledObject {
+    pub name: String,
+    //"type" before Tiled 1.9, "class" after
+    pub kind: String,
+    //In pixels, tile objects are anchored at their bottom left instead of top left
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub gid: Option<u32>,
+    pub properties: Properties,
+}
+
+#[derive(Debug)]
+pub enum TiledError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+    Unsupported(PathBuf, String),
+}
+
+impl fmt::Display for TiledError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TiledError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            TiledError::Parse(path, message) => write!(f, "{}: {}", path.display(), message),
+            TiledError::Unsupported(path, message) => {
+                write!(f, "{}: unsupported, {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TiledError {}
+
+//Contents of the external tileset files a map uses, keyed by their resolved path
+pub type TilesetFiles = HashMap<PathBuf, String>;
+
+impl TiledMap {
+    //Reads the map and its external tilesets from disk
+    pub fn load(path: &Path) -> Result<TiledMap, TiledError> {
+        let contents = read(path)?;
+        let mut files = TilesetFiles::new();
+        for source in TiledMap::tileset_sources(&contents, path)? {
+            let tileset = read(&source)?;
+            files.insert(source, tileset);
+        }
+        TiledMap::parse(&contents, path, &files)
+    }
+
+    //External tilesets the map refers to, resolved against its path.
+    //Their contents are read by the caller so parse never touches the disk
+    pub fn tileset_sources(contents: &str, path: &Path) -> Result<Vec<PathBuf>, TiledError> {
+        let sources = match format(path)? {
+            Format::Tmj => {
+                let map: TmjSources = serde_json::from_str(contents)
+                    .map_err(|err| TiledError::Parse(path.to_path_buf(), err.to_string()))?;
+                map.tilesets
+                    .into_iter()
+                    .filter_map(|tileset| tileset.source)
+                    .collect()
+            }
+            Format::Tmx => {
+                let document = roxmltree::Document::parse(contents)
+                    .map_err(|err| TiledError::Parse(path.to_path_buf(), err.to_string()))?;
+                let root = document.root_element();
+                root.children()
+                    .filter(|node| node.is_element() && node.tag_name().name() == "tileset")
+                    .filter_map(|node| node.attribute("source").map(str::to_string))
+                    .collect::<Vec<_>>()
+            }
+        };
+        Ok(sources
+            .iter()
+            .map(|source| relative_to(path, source))
+            .collect())
+    }
+
+    //files holds every tileset listed by tileset_sources
+    pub fn parse(
+        contents: &str,
+        path: &Path,
+        files: &TilesetFiles,
+    ) -> Result<TiledMap, TiledError> {
+        match format(path)? {
+            Format::Tmj => parse_tmj(contents, path, files),
+            Format::Tmx => parse_tmx(contents, path, files),
+        }
+    }
+
+    //The tileset a gid belongs to and the tile id inside of it
+    pub fn tile(&self, gid: u32) -> Option<(&TiledTileset, u32)> {
+        if gid == 0 {
+            return None;
+        }
+        self.tilesets
+            .iter()
+            .filter(|tileset| tileset.first_gid <= gid)
+            .max_by_key(|tileset| tileset.first_gid)
+            .map(|tileset| (tileset, gid - tileset.first_gid))
+    }
+
+    //Top left tile covered by an object
+    pub fn object_tile(&self, object: &TiledObject) -> (i32, i32) {
+        let y = if object.gid.is_some() {
+            object.y - object.height
+        } else {
+            object.y
+        };
+        (
+            (object.x / self.tile_width as f32).floor() as i32,
+            (y / self.tile_height as f32).floor() as i32,
+        )
+    }
+
+    //Every tile an object covers, point objects cover the tile they are on
+    pub fn object_tiles(&self, object: &TiledObject) -> Vec<(i32, i32)> {
+        let (x, y) = self.object_tile(object);
+        let width = (object.width / self.tile_width as f32).ceil().max(1.0) as i32;
+        let height = (object.height / self.tile_height as f32).ceil().max(1.0) as i32;
+        let mut tiles = Vec::new();
+        for dy in 0..height {
+            for dx in 0..width {
+                tiles.push((x + dx, y + dy));
+            }
+        }
+        tiles
+    }
+}
+
+//Tile layers are matched to our layers by name, otherwise the first one is the ground.
+//Tile properties: collider (bool), zone (encounter zone name), object (sign, chest, door,
+//switch, npc) and toggle (tile id it turns into)
+pub struct TiledTiles {
+    pub data: MapData,
+    pub legend: Legend,
+    //Gids past the private use area, they are left out
+    pub skipped: Vec<u32>,
+}
+
+impl TiledMap {
+    pub fn import_tiles(&self) -> TiledTiles {
+        let mut tiles = TiledTiles {
+            data: MapData::new(self.width, self.height),
+            legend: Legend::default(),
+            skipped: Vec::new(),
+        };
+
+        let mut first_tile_layer = true;
+        for layer in self.layers.iter() {
+            let (name, gids, properties) = match layer {
+                TiledLayer::Tiles {
+                    name,
+                    data,
+                    properties,
+                } => (name, data, properties),
+                TiledLayer::Objects { .. } => continue,
+            };
+            let layer_name = properties.get("layer").unwrap_or(name).to_lowercase();
+            let target = match layer_name.as_str() {
+                "ground" => MapLayer::Ground,
+                "decoration" => MapLayer::Decoration,
+                "collision" => MapLayer::Collision,
+                "objects" => MapLayer::Objects,
+                _ if first_tile_layer => MapLayer::Ground,
+                _ => MapLayer::Decoration,
+            };
+            first_tile_layer = false;
+
+            for (i, gid) in gids.iter().copied().enumerate() {
+                let x = (i % self.width.max(1)) as i32;
+                let y = (i / self.width.max(1)) as i32;
+                if gid == 0 {
+                    continue;
+                }
+                if target == MapLayer::Collision {
+                    tiles.data.set(target, x, y, '#');
+                    continue;
+                }
+                match glyph(gid) {
+                    Some(glyph) => {
+                        if tiles.legend.get(glyph).is_none() {
+                            tiles.legend.0.insert(glyph, self.legend_entry(gid));
+                        }
+                        tiles.data.set(target, x, y, glyph);
+                    }
+                    None if !tiles.skipped.contains(&gid) => tiles.skipped.push(gid),
+                    None => {}
+                }
+            }
+        }
+        tiles
+    }
+
+    pub fn legend_entry(&self, gid: u32) -> LegendEntry {
+        let (tileset, id) = match self.tile(gid) {
+            Some(tile) => tile,
+            None => {
+                return LegendEntry {
+                    name: format!("Missing tile {}", gid),
+                    sprite: None,
+                    color: (1.0, 0.0, 1.0),
+                    collider: true,
+                    zone: None,
+                    object: None,
+                    toggle: None,
+                    tile: None,
+                    animation: None,
+                }
+            }
+        };
+        let properties = tileset.tile_properties.get(&id);
+        let property = |name: &str| properties.and_then(|properties| properties.get(name));
+
+        let collider = property("collider").is_some_and(|value| value == "true");
+        let zone = property("zone").cloned();
+        let object = property("object").and_then(|object| object_type(object));
+        let toggle = property("toggle")
+            .and_then(|id| id.parse::<u32>().ok())
+            .and_then(|id| glyph(tileset.first_gid + id));
+        //Shown when the ascii renderer is used
+        let fallback = match object {
+            Some(ObjectType::Sign) => '!',
+            Some(ObjectType::Chest) => '=',
+            Some(ObjectType::Door) => '+',
+            Some(ObjectType::Switch) => '/',
+            Some(ObjectType::Npc) => '@',
+            Some(ObjectType::Spawn) => '$',
+            None if collider => '#',
+            None if zone.is_some() => '~',
+            None => '.',
+        };
+        LegendEntry {
+            name: format!("{} {}", tileset.name, id),
+            sprite: Some(fallback as usize),
+            color: (1.0, 1.0, 1.0),
+            collider,
+            zone,
+            object,
+            toggle,
+            tile: Some((asset_path(&tileset.image), id as usize)),
+            animation: None,
+        }
+    }
+
+    //Every object of every object layer
+    pub fn objects(&self) -> impl Iterator<Item = &TiledObject> {
+        self.layers
+            .iter()
+            .filter_map(|layer| match layer {
+                TiledLayer::Objects { objects, .. } => Some(objects),
+                TiledLayer::Tiles { .. } => None,
+            })
+            .flatten()
+    }
+}
+
+//Tiled tiles have no glyph, so each gid gets one from the unicode private use area
+pub fn glyph(gid: u32) -> Option<char> {
+    char::from_u32(0xE000 + gid).filter(|glyph| *glyph <= '\u{F8FF}')
+}
+
+//Tiled image paths are relative to the map, the asset server wants them relative to assets/
+pub fn asset_path(image: &Path) -> String {
+    image
+        .strip_prefix("assets")
+        .unwrap_or(image)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+//Tile lists in object properties, "x,y x,y"
+pub fn parse_tiles(list: &str) -> Vec<(i32, i32)> {
+    list.split_whitespace()
+        .filter_map(|pair| {
+            let (x, y) = pair.split_once(',')?;
+            Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+fn object_type(name: &str) -> Option<ObjectType> {
+    match name.to_lowercase().as_str() {
+        "sign" => Some(ObjectType::Sign),
+        "chest" => Some(ObjectType::Chest),
+        "door" => Some(ObjectType::Door),
+        "switch" => Some(ObjectType::Switch),
+        "npc" => Some(ObjectType::Npc),
+        "spawn" => Some(ObjectType::Spawn),
+        _ => None,
+    }
+}
+
+//Collapses the ".." Tiled likes to put in image paths
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            Component::CurDir => {}
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+enum Format {
+    Tmj,
+    Tmx,
+}
+
+fn format(path: &Path) -> Result<Format, TiledError> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("tmj") | Some("json") => Ok(Format::Tmj),
+        Some("tmx") => Ok(Format::Tmx),
+        _ => Err(TiledError::Unsupported(
+            path.to_path_buf(),
+            "expected a .tmj or .tmx file".to_string(),
+        )),
+    }
+}
+
+fn read(path: &Path) -> Result<String, TiledError> {
+    fs::read_to_string(path).map_err(|err| TiledError::Io(path.to_path_buf(), err))
+}
+
+fn relative_to(file: &Path, other: &str) -> PathBuf {
+    normalize(&file.parent().unwrap_or_else(|| Path::new("")).join(other))
+}
+
+#[derive(Deserialize)]
+struct TmjProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct TmjTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<TmjProperty>,
+}
+
+#[derive(Deserialize)]
+struct TmjTileset {
+    #[serde(default)]
+    firstgid: u32,
+    source: Option<String>,
+    #[serde(default)]
+    name: String,
+    image: Option<String>,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    #[serde(default)]
+    columns: u32,
+    #[serde(default)]
+    tilecount: u32,
+    #[serde(default)]
+    spacing: u32,
+    #[serde(default)]
+    tiles: Vec<TmjTile>,
+}
+
+#[derive(Deserialize)]
+struct TmjObject {
+    #[serde(default)]
+    name: String,
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    gid: Option<u32>,
+    #[serde(default)]
+    properties: Vec<TmjProperty>,
+}
+
+#[derive(Deserialize)]
+struct TmjLayer {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    data: Option<serde_json::Value>,
+    encoding: Option<String>,
+    #[serde(default)]
+    objects: Vec<TmjObject>,
+    #[serde(default)]
+    layers: Vec<TmjLayer>,
+    #[serde(default)]
+    properties: Vec<TmjProperty>,
+}
+
+//Only the tileset sources, read before the rest of the map
+#[derive(Deserialize)]
+struct TmjSources {
+    #[serde(default)]
+    tilesets: Vec<TmjSource>,
+}
+
+#[derive(Deserialize)]
+struct TmjSource {
+    source: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TmjMap {
+    width: usize,
+    height: usize,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    infinite: bool,
+    layers: Vec<TmjLayer>,
+    #[serde(default)]
+    tilesets: Vec<TmjTileset>,
+}
+
+fn tmj_properties(properties: &[TmjProperty]) -> Properties {
+    properties
+        .iter()
+        .map(|property| {
+            let value = match &property.value {
+                serde_json::Value::String(value) => value.clone(),
+                other => other.to_string(),
+            };
+            (property.name.clone(), value)
+        })
+        .collect()
+}
+
+fn parse_tmj(contents: &str, path: &Path, files: &TilesetFiles) -> Result<TiledMap, TiledError> {
+    let map: TmjMap = serde_json::from_str(contents)
+        .map_err(|err| TiledError::Parse(path.to_path_buf(), err.to_string()))?;
+    if map.infinite {
+        return Err(TiledError::Unsupported(
+            path.to_path_buf(),
+            "infinite maps".to_string(),
+        ));
+    }
+
+    let mut tilesets = Vec::new();
+    for tileset in map.tilesets {
+        let tileset = match &tileset.source {
+            Some(source) => {
+                let mut external = load_external_tileset(&relative_to(path, source), files)?;
+                external.first_gid = tileset.firstgid;
+                external
+            }
+            None => tmj_tileset(tileset, path)?,
+        };
+        tilesets.push(tileset);
+    }
+
+    let mut layers = Vec::new();
+    flatten_tmj_layers(map.layers, path, &mut layers)?;
+    Ok(TiledMap {
+        width: map.width,
+        height: map.height,
+        tile_width: map.tilewidth,
+        tile_height: map.tileheight,
+        tilesets,
+        layers,
+    })
+}
+
+fn tmj_tileset(tileset: TmjTileset, path: &Path) -> Result<TiledTileset, TiledError> {
+    let image = tileset.image.ok_or_else(|| {
+        TiledError::Unsupported(
+            path.to_path_buf(),
+            format!("tileset {} is a collection of images", tileset.name),
+        )
+    })?;
+    Ok(TiledTileset {
+        first_gid: tileset.firstgid,
+        name: tileset.name,
+        image: relative_to(path, &image),
+        tile_width: tileset.tilewidth,
+        tile_height: tileset.tileheight,
+        columns: tileset.columns,
+        tile_count: tileset.tilecount,
+        spacing: tileset.spacing,
+        tile_properties: tileset
+            .tiles
+            .iter()
+            .map(|tile| (tile.id, tmj_properties(&tile.properties)))
+            .collect(),
+    })
+}
+
+//Groups are flattened, their children keep their own names
+fn flatten_tmj_layers(
+    source: Vec<TmjLayer>,
+    path: &Path,
+    layers: &mut Vec<TiledLayer>,
+) -> Result<(), TiledError> {
+    for layer in source {
+        match layer.kind.as_str() {
+            "tilelayer" => {
+                if layer
+                    .encoding
+                    .as_deref()
+                    .is_some_and(|encoding| encoding != "csv")
+                {
+                    return Err(TiledError::Unsupported(
+                        path.to_path_buf(),
+                        format!("layer {} is compressed, save it as CSV", layer.name),
+                    ));
+                }
+                let data: Vec<u32> = match layer.data {
+                    Some(data) => serde_json::from_value(data)
+                        .map_err(|err| TiledError::Parse(path.to_path_buf(), err.to_string()))?,
+                    None => Vec::new(),
+                };
+                layers.push(TiledLayer::Tiles {
+                    name: layer.name,
+                    data: data.into_iter().map(|gid| gid & !FLIP_FLAGS).collect(),
+                    properties: tmj_properties(&layer.properties),
+                });
+            }
+            "objectgroup" => layers.push(TiledLayer::Objects {
+                name: layer.name,
+                objects: layer
+                    .objects
+                    .iter()
+                    .map(|object| TiledObject {
+                        name: object.name.clone(),
+                        kind: if object.class.is_empty() {
+                            object.kind.clone()
+                        } else {
+                            object.class.clone()
+                        },
+                        x: object.x,
+                        y: object.y,
+                        width: object.width,
+                        height: object.height,
+                        gid: object.gid.map(|gid| gid & !FLIP_FLAGS),
+                        properties: tmj_properties(&object.properties),
+                    })
+                    .collect(),
+            }),
+            "group" => flatten_tmj_layers(layer.layers, path, layers)?,
+            //Image layers have nothing the game can use
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn load_external_tileset(path: &Path, files: &TilesetFiles) -> Result<TiledTileset, TiledError> {
+    let contents = files.get(path).ok_or_else(|| {
+        TiledError::Parse(path.to_path_buf(), "tileset file was not read".to_string())
+    })?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("tsx") => {
+            let document = roxmltree::Document::parse(contents)
+                .map_err(|err| TiledError::Parse(path.to_path_buf(), err.to_string()))?;
+            tmx_tileset(document.root_element(), path)
+        }
+        _ => {
+            let tileset: TmjTileset = serde_json::from_str(contents)
+                .map_err(|err| TiledError::Parse(path.to_path_buf(), err.to_string()))?;
+            tmj_tileset(tileset, path)
+        }
+    }
+}
+
+fn attribute<T: std::str::FromStr>(node: roxmltree::Node, name: &str) -> Option<T> {
+    node.attribute(name).and_then(|value| value.parse().ok())
+}
+
+fn required<T: std::str::FromStr>(
+    node: roxmltree::Node,
+    name: &str,
+    path: &Path,
+) -> Result<T, TiledError> {
+    attribute(node, name).ok_or_else(|| {
+        TiledError::Parse(
+            path.to_path_buf(),
+            format!(
+                "<{}> is missing a valid {} attribute",
+                node.tag_name().name(),
+                name
+            ),
+        )
+    })
+}
+
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn tmx_properties(node: roxmltree::Node) -> Properties {
+    let properties = match child(node, "properties") {
+        Some(properties) => properties,
+        None => return Properties::new(),
+    };
+    properties
+        .children()
+        .filter(|property| property.is_element() && property.tag_name().name() == "property")
+        .filter_map(|property| {
+            let name = property.attribute("name")?.to_string();
+            //Multiline strings are stored as text instead of an attribute
+            let value = property
+                .attribute("value")
+                .or_else(|| property.text())
+                .unwrap_or_default()
+                .to_string();
+            Some((name, value))
+        })
+        .collect()
+}
+
+fn tmx_tileset(node: roxmltree::Node, path: &Path) -> Result<TiledTileset, TiledError> {
+    let name = node.attribute("name").unwrap_or_default().to_string();
+    let image = child(node, "image").ok_or_else(|| {
+        TiledError::Unsupported(
+            path.to_path_buf(),
+            format!("tileset {} is a collection of images", name),
+        )
+    })?;
+    let tile_properties = node
+        .children()
+        .filter(|tile| tile.is_element() && tile.tag_name().name() == "tile")
+        .filter_map(|tile| Some((attribute(tile, "id")?, tmx_properties(tile))))
+        .collect();
+    Ok(TiledTileset {
+        first_gid: attribute(node, "firstgid").unwrap_or(0),
+        name,
+        image: relative_to(path, &required::<String>(image, "source", path)?),
+        tile_width: required(node, "tilewidth", path)?,
+        tile_height: required(node, "tileheight", path)?,
+        columns: required(node, "columns", path)?,
+        tile_count: required(node, "tilecount", path)?,
+        spacing: attribute(node, "spacing").unwrap_or(0),
+        tile_properties,
+    })
+}
+
+fn parse_tmx(contents: &str, path: &Path, files: &TilesetFiles) -> Result<TiledMap, TiledError> {
+    let document = roxmltree::Document::parse(contents)
+        .map_err(|err| TiledError::Parse(path.to_path_buf(), err.to_string()))?;
+    let root = document.root_element();
+    if root.attribute("infinite") == Some("1") {
+        return Err(TiledError::Unsupported(
+            path.to_path_buf(),
+            "infinite maps".to_string(),
+        ));
+    }
+
+    let mut tilesets = Vec::new();
+    for node in root
+        .children()
+        .filter(|node| node.is_element() && node.tag_name().name() == "tileset")
+    {
+        let tileset = match node.attribute("source") {
+            Some(source) => {
+                let mut external = load_external_tileset(&relative_to(path, source), files)?;
+                external.first_gid = required(node, "firstgid", path)?;
+                external
+            }
+            None => tmx_tileset(node, path)?,
+        };
+        tilesets.push(tileset);
+    }
+
+    let mut layers = Vec::new();
+    flatten_tmx_layers(root, path, &mut layers)?;
+    Ok(TiledMap {
+        width: required(root, "width", path)?,
+        height: required(root, "height", path)?,
+        tile_width: required(root, "tilewidth", path)?,
+        tile_height: required(root, "tileheight", path)?,
+        tilesets,
+        layers,
+    })
+}
+
+fn flatten_tmx_layers(
+    parent: roxmltree::Node,
+    path: &Path,
+    layers: &mut Vec<TiledLayer>,
+) -> Result<(), TiledError> {
+    for node in parent.children().filter(|node| node.is_element()) {
+        let name = node.attribute("name").unwrap_or_default().to_string();
+        match node.tag_name().name() {
+            "layer" => {
+                let data = child(node, "data").ok_or_else(|| {
+                    TiledError::Parse(path.to_path_buf(), format!("layer {} has no data", name))
+                })?;
+                let gids: Vec<u32> = match data.attribute("encoding") {
+                    Some("csv") => data
+                        .text()
+                        .unwrap_or_default()
+                        .split(',')
+                        .map(|gid| gid.trim())
+                        .filter(|gid| !gid.is_empty())
+                        .map(|gid| {
+                            gid.parse().map_err(|_| {
+                                TiledError::Parse(
+                                    path.to_path_buf(),
+                                    format!("layer {} has a bad gid {:?}", name, gid),
+                                )
+                            })
+                        })
+                        .collect::<Result<_, _>>()?,
+                    None => data
+                        .children()
+                        .filter(|tile| tile.is_element() && tile.tag_name().name() == "tile")
+                        .map(|tile| attribute(tile, "gid").unwrap_or(0))
+                        .collect(),
+                    Some(_) => {
+                        return Err(TiledError::Unsupported(
+                            path.to_path_buf(),
+                            format!("layer {} is compressed, save it as CSV", name),
+                        ))
+                    }
+                };
+                layers.push(TiledLayer::Tiles {
+                    name,
+                    data: gids.into_iter().map(|gid| gid & !FLIP_FLAGS).collect(),
+                    properties: tmx_properties(node),
+                });
+            }
+            "objectgroup" => {
+                let objects = node
+                    .children()
+                    .filter(|object| object.is_element() && object.tag_name().name() == "object")
+                    .map(|object| TiledObject {
+                        name: object.attribute("name").unwrap_or_default().to_string(),
+                        kind: object
+                            .attribute("class")
+                            .or_else(|| object.attribute("type"))
+                            .unwrap_or_default()
+                            .to_string(),
+                        x: attribute(object, "x").unwrap_or(0.0),
+                        y: attribute(object, "y").unwrap_or(0.0),
+                        width: attribute(object, "width").unwrap_or(0.0),
+                        height: attribute(object, "height").unwrap_or(0.0),
+                        gid: attribute::<u32>(object, "gid").map(|gid| gid & !FLIP_FLAGS),
+                        properties: tmx_properties(object),
+                    })
+                    .collect();
+                layers.push(TiledLayer::Objects { name, objects });
+            }
+            "group" => flatten_tmx_layers(node, path, layers)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TMJ: &str = r#"{
+        "width": 2, "height": 2, "tilewidth": 16, "tileheight": 16,
+        "tilesets": [
+            {"firstgid": 1, "name": "terrain", "image": "../tilesets/terrain.png",
+             "tilewidth": 16, "tileheight": 16, "columns": 4, "tilecount": 8,
+             "tiles": [{"id": 1, "properties": [{"name": "collider", "type": "bool", "value": true}]}]},
+            {"firstgid": 9, "source": "../tilesets/props.tsx"}
+        ],
+        "layers": [
+            {"type": "tilelayer", "name": "ground", "data": [1, 2147483650, 0, 9]},
+            {"type": "group", "name": "decor", "layers": [
+                {"type": "objectgroup", "name": "markers", "objects": [
+                    {"name": "spawn", "type": "spawn", "x": 16, "y": 0},
+                    {"name": "chest", "class": "chest", "type": "old", "x": 0, "y": 32,
+                     "width": 16, "height": 16, "gid": 1073741833}
+                ]}
+            ]},
+            {"type": "imagelayer", "name": "sky"}
+        ]
+    }"#;
+
+    const TSX: &str = r#"<tileset name="props" tilewidth="16" tileheight="16" columns="2" tilecount="4">
+        <image source="props.png" width="32" height="32"/>
+        <tile id="0"><properties><property name="zone" value="forest"/></properties></tile>
+    </tileset>"#;
+
+    const TMX: &str = r#"<map width="2" height="2" tilewidth="16" tileheight="16">
+        <tileset firstgid="1" source="../tilesets/props.tsx"/>
+        <layer name="ground">
+            <data encoding="csv">1,2,
+3,1073741825</data>
+        </layer>
+        <group name="upper">
+            <layer name="roof">
+                <data><tile gid="4"/><tile/><tile gid="2147483650"/><tile gid="0"/></data>
+            </layer>
+        </group>
+        <objectgroup name="markers">
+            <object name="exit" type="warp" x="0" y="16" width="32" height="16"/>
+        </objectgroup>
+    </map>"#;
+
+    fn files() -> TilesetFiles {
+        let mut files = TilesetFiles::new();
+        files.insert(PathBuf::from("tilesets/props.tsx"), TSX.to_string());
+        files
+    }
+
+    fn tiles(map: &TiledMap, index: usize) -> &[u32] {
+        match &map.layers[index] {
+            TiledLayer::Tiles { data, .. } => data,
+            other => panic!("expected a tile layer, got {:?}", other),
+        }
+    }
+
+    fn objects(map: &TiledMap, index: usize) -> &[TiledObject] {
+        match &map.layers[index] {
+            TiledLayer::Objects { objects, .. } => objects,
+            other => panic!("expected an object layer, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lists_external_tilesets() {
+        let sources = TiledMap::tileset_sources(TMJ, Path::new("maps/test.tmj")).unwrap();
+        assert_eq!(sources, vec![PathBuf::from("tilesets/props.tsx")]);
+        let sources = TiledMap::tileset_sources(TMX, Path::new("maps/test.tmx")).unwrap();
+        assert_eq!(sources, vec![PathBuf::from("tilesets/props.tsx")]);
+    }
+
+    #[test]
+    fn parses_tmj() {
+        let map = TiledMap::parse(TMJ, Path::new("maps/test.tmj"), &files()).unwrap();
+        assert_eq!((map.width, map.height), (2, 2));
+        assert_eq!(map.tilesets[0].image, PathBuf::from("tilesets/terrain.png"));
+        assert_eq!(map.tilesets[0].tile_properties[&1]["collider"], "true");
+        //The external tileset takes its first gid from the map
+        assert_eq!(map.tilesets[1].first_gid, 9);
+        assert_eq!(map.tilesets[1].image, PathBuf::from("tilesets/props.png"));
+        assert_eq!(map.tilesets[1].tile_properties[&0]["zone"], "forest");
+        //The group is flattened and the image layer dropped
+        assert_eq!(map.layers.len(), 2);
+        assert_eq!(tiles(&map, 0), &[1, 2, 0, 9]);
+    }
+
+    #[test]
+    fn parses_tmx() {
+        let map = TiledMap::parse(TMX, Path::new("maps/test.tmx"), &files()).unwrap();
+        assert_eq!(map.tilesets[0].first_gid, 1);
+        assert_eq!(map.tilesets[0].name, "props");
+        assert_eq!(map.layers.len(), 3);
+        assert_eq!(tiles(&map, 0), &[1, 2, 3, 1]);
+        assert_eq!(tiles(&map, 1), &[4, 0, 2, 0]);
+        assert_eq!(objects(&map, 2)[0].kind, "warp");
+    }
+
+    #[test]
+    fn missing_tileset_file_is_an_error() {
+        let result = TiledMap::parse(TMJ, Path::new("maps/test.tmj"), &TilesetFiles::new());
+        assert!(matches!(result, Err(TiledError::Parse(..))));
+    }
+
+    #[test]
+    fn rejects_compressed_layers() {
+        let tmx = TMX.replace("encoding=\"csv\"", "encoding=\"base64\"");
+        let result = TiledMap::parse(&tmx, Path::new("maps/test.tmx"), &files());
+        assert!(matches!(result, Err(TiledError::Unsupported(..))));
+    }
+
+    #[test]
+    fn class_wins_over_type() {
+        let map = TiledMap::parse(TMJ, Path::new("maps/test.tmj"), &files()).unwrap();
+        let markers = objects(&map, 1);
+        assert_eq!(markers[0].kind, "spawn");
+        assert_eq!(markers[1].kind, "chest");
+        assert_eq!(markers[1].gid, Some(9));
+    }
+
+    #[test]
+    fn tile_objects_are_anchored_at_the_bottom() {
+        let map = TiledMap::parse(TMJ, Path::new("maps/test.tmj"), &files()).unwrap();
+        let markers = objects(&map, 1);
+        assert_eq!(map.object_tile(&markers[0]), (1, 0));
+        assert_eq!(map.object_tile(&markers[1]), (0, 1));
+        assert_eq!(map.object_tiles(&markers[0]), vec![(1, 0)]);
+
+        let map = TiledMap::parse(TMX, Path::new("maps/test.tmx"), &files()).unwrap();
+        assert_eq!(map.object_tiles(&objects(&map, 2)[0]), vec![(0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn finds_the_tileset_of_a_gid() {
+        let map = TiledMap::parse(TMJ, Path::new("maps/test.tmj"), &files()).unwrap();
+        assert!(map.tile(0).is_none());
+        let (tileset, id) = map.tile(2).unwrap();
+        assert_eq!((tileset.name.as_str(), id), ("terrain", 1));
+        let (tileset, id) = map.tile(10).unwrap();
+        assert_eq!((tileset.name.as_str(), id), ("props", 1));
+    }
+
+    #[test]
+    fn normalizes_parent_dirs() {
+        assert_eq!(
+            normalize(Path::new("maps/../tilesets/./a.png")),
+            PathBuf::from("tilesets/a.png")
+        );
+        assert_eq!(normalize(Path::new("../a.png")), PathBuf::from("../a.png"));
+    }
+}
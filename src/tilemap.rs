@@ -1,82 +1,1020 @@
 use std::{
-    fs::File,
-    io::{BufRead, BufReader},
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
 };
 
-use bevy::prelude::*;
+use bevy::{asset::LoadState, ecs::system::SystemParam, prelude::*, sprite::Mesh2dHandle};
+use bevy_2d_rpg::{
+    collision::CollisionMap,
+    dungeon::{self, DungeonSettings},
+    map::{Legend, LegendEntry, MapData, MapLayer, ObjectType, DEFAULT_SPAWN, EMPTY},
+    quads::{chunk_of, chunk_tiles, map_chunks, set_quad_index, Quad},
+    tiled::{asset_path, parse_tiles, TiledMap, TiledTiles},
+};
+use serde::{Deserialize, Serialize, Serializer};
 
 use crate::{
-    ascii::{spawn_ascii_sprite, AsciiSheet},
-    npc::Npc,
+    ascii::AsciiSheet,
+    batch::MeshBatcher,
+    camera::CameraController,
+    combat::EnemyType,
+    encounter::{EncounterZones, ZonesFile},
+    fadeout::create_warp_fadeout,
+    graphics::{CharacterSheet, TileClock, TileSheet, TileStyle},
+    input::{InputAction, InputActions},
+    interact::Interactable,
+    map_asset::{MapAsset, MapAssetLoader, RonAsset, RonAssetLoader, TiledAsset, TiledAssetLoader},
+    npc::{spawn_npc, NpcMovement},
+    player::Player,
+    roamer::RoamerSpawn,
+    save::WorldFlags,
     GameState, TILE_SIZE,
 };
 
 pub struct TileMapPlugin;
 
 #[derive(Component)]
-pub struct EncounterSpawner;
+pub struct Map;
 
-#[derive(Component)]
-pub struct TileCollider;
+//Tiles are drawn as one mesh per chunk, layer and texture instead of a sprite each
+#[derive(Default)]
+pub struct MapChunks {
+    map: Option<Entity>,
+    meshes: HashMap<(i32, i32), Vec<Entity>>,
+    //Remeshed at the end of the frame
+    dirty: HashSet<(i32, i32)>,
+    //Where each animated glyph was drawn, its frames are swapped in place
+    animated: HashMap<char, Vec<AnimatedQuads>>,
+}
 
-#[derive(Component)]
-struct Map;
+//Quads of one mesh of a chunk, with the animated glyphs noted by the number of their quad
+type ChunkBatch = (Handle<TextureAtlas>, Color, Vec<Quad>, Vec<(char, usize)>);
+
+//Quads of one chunk mesh showing the same animated glyph
+struct AnimatedQuads {
+    chunk: (i32, i32),
+    batch: Entity,
+    //Counted in the order they were given to the mesh
+    quads: Vec<usize>,
+}
+
+impl MapChunks {
+    pub fn mark_dirty(&mut self, position: (i32, i32)) {
+        self.dirty.insert(chunk_of(position));
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        let chunks = self.meshes.keys().copied().collect::<Vec<_>>();
+        self.dirty.extend(chunks);
+    }
+}
+
+pub const START_MAP: &str = "town";
+//Where the stairs up on the first floor of a generated dungeon lead
+const DUNGEON_ENTRANCE: (&str, &str) = ("cellar", "dungeon");
+const LEGEND_PATH: &str = "maps/legend.ron";
+//Tried in order, our own .map files win over Tiled maps of the same name
+const MAP_EXTENSIONS: [&str; 3] = ["map", "tmj", "tmx"];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Warp {
+    pub map: String,
+    pub spawn: String,
+}
+
+//Sent by the fadeout once the screen is dark
+pub struct WarpEvent(pub Warp);
+
+//A map to spawn as soon as its file has loaded, and where to put the player in it
+pub struct PendingMap {
+    pub name: String,
+    pub spawn: Option<String>,
+    //Held so the asset isn't dropped while it loads
+    source: MapSource,
+    //Index into MAP_EXTENSIONS of the file being loaded
+    extension: usize,
+    //The <name>.ron next to a .map, only looked for once the .map has loaded
+    objects: Option<Handle<RonAsset>>,
+}
+
+enum MapSource {
+    Text(Handle<MapAsset>),
+    Tiled(Handle<TiledAsset>),
+    //Dungeon floors are generated from their name
+    Generated,
+}
+
+//Maps live in assets/maps/<name>.map with their objects in <name>.ron,
+//or come from the Tiled editor as <name>.tmj or <name>.tmx
+#[derive(Default)]
+pub struct CurrentMap {
+    pub name: String,
+    //Watched for hot reloading, None for Tiled maps
+    pub handle: Option<Handle<MapAsset>>,
+    //Its objects file, also watched, None when the map has none
+    pub objects_handle: Option<Handle<RonAsset>>,
+    //Kept in sync with the tile meshes whenever something is opened or switched
+    pub data: MapData,
+    //The map as written in its file, before world flags were applied, which is what gets saved
+    pub source: MapData,
+    //The shared legend, or the one made up for a Tiled map
+    pub legend: Legend,
+    pub warps: HashMap<(i32, i32), Warp>,
+    pub spawns: HashMap<String, (i32, i32)>,
+    pub roamers: Vec<RoamerSpawn>,
+    //Hidden under fog until the player has seen it
+    pub dark: bool,
+    //The objects file as written, which saving starts from
+    objects_source: MapObjects,
+}
+
+//Extra data for the objects of a map, keyed by tile position
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct MapObjects {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    dark: bool,
+    #[serde(default)]
+    #[serde(serialize_with = "sorted", skip_serializing_if = "HashMap::is_empty")]
+    signs: HashMap<(i32, i32), String>,
+    #[serde(default)]
+    #[serde(serialize_with = "sorted", skip_serializing_if = "HashMap::is_empty")]
+    chests: HashMap<(i32, i32), String>,
+    #[serde(default)]
+    #[serde(serialize_with = "sorted", skip_serializing_if = "HashMap::is_empty")]
+    switches: HashMap<(i32, i32), Vec<(i32, i32)>>,
+    #[serde(default)]
+    #[serde(serialize_with = "sorted", skip_serializing_if = "HashMap::is_empty")]
+    warps: HashMap<(i32, i32), Warp>,
+    #[serde(default)]
+    #[serde(serialize_with = "sorted", skip_serializing_if = "HashMap::is_empty")]
+    spawns: HashMap<String, (i32, i32)>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    roamers: Vec<RoamerSpawn>,
+    #[serde(default)]
+    #[serde(serialize_with = "sorted", skip_serializing_if = "HashMap::is_empty")]
+    npcs: HashMap<(i32, i32), NpcMovement>,
+}
+
+//Saved files would otherwise shuffle their entries on every save
+fn sorted<K: Ord + Serialize, V: Serialize, S: Serializer>(
+    map: &HashMap<K, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
+
+impl MapObjects {
+    fn interactable_at(&self, position: (i32, i32), object: ObjectType) -> Option<Interactable> {
+        match object {
+            ObjectType::Sign => Some(Interactable::Sign(
+                self.signs.get(&position).cloned().unwrap_or_default(),
+            )),
+            ObjectType::Chest => Some(Interactable::Chest(
+                self.chests.get(&position).cloned().unwrap_or_default(),
+            )),
+            ObjectType::Door => Some(Interactable::Door),
+            ObjectType::Switch => Some(Interactable::Switch(
+                self.switches.get(&position).cloned().unwrap_or_default(),
+            )),
+            ObjectType::Npc | ObjectType::Spawn => None,
+        }
+    }
+}
+
+pub fn world_to_tile(translation: Vec3) -> (i32, i32) {
+    (
+        (translation.x / TILE_SIZE).round() as i32,
+        (-translation.y / TILE_SIZE).round() as i32,
+    )
+}
+
+pub fn tile_to_world(tile: (i32, i32), z: f32) -> Vec3 {
+    Vec3::new(tile.0 as f32 * TILE_SIZE, -(tile.1 as f32) * TILE_SIZE, z)
+}
 
 impl Plugin for TileMapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
-            SystemSet::on_enter(GameState::Overworld).with_system(create_simple_map),
-        )
-        .add_system_set(SystemSet::on_resume(GameState::Overworld).with_system(show_map))
-        .add_system_set(SystemSet::on_pause(GameState::Overworld).with_system(hide_map));
-    }
-}
-
-fn create_simple_map(mut commands: Commands, ascii: Res<AsciiSheet>) {
-    let file = File::open("assets/map.txt").expect("No map file found");
-    let mut tiles = Vec::new();
-
-    for (y, line) in BufReader::new(file).lines().enumerate() {
-        if let Ok(line) = line {
-            for (x, char) in line.chars().enumerate() {
-                let color = match char {
-                    '#' => Color::rgb(0.7, 0.7, 0.7),
-                    '@' => Color::rgb(0.5, 0.5, 0.2),
-                    '~' => Color::rgb(0.2, 0.9, 0.2),
-                    _ => Color::rgb(0.9, 0.9, 0.9),
+        app.add_asset::<MapAsset>()
+            .init_asset_loader::<MapAssetLoader>()
+            .add_asset::<TiledAsset>()
+            .init_asset_loader::<TiledAssetLoader>()
+            //Also reads encounter_zones.ron for the EncounterPlugin
+            .add_asset::<RonAsset>()
+            .init_asset_loader::<RonAssetLoader>()
+            .init_resource::<CollisionMap>()
+            .init_resource::<MapChunks>()
+            //Empty until the first map has loaded
+            .init_resource::<CurrentMap>()
+            //Empty until legend.ron has loaded, maps wait for it
+            .init_resource::<Legend>()
+            .add_event::<WarpEvent>()
+            .add_startup_system(load_legend)
+            .add_system(update_legend)
+            .add_system(reload_changed_maps)
+            .add_system_to_stage(CoreStage::PostUpdate, mesh_map_chunks)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Overworld).with_system(create_simple_map),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Overworld)
+                    .with_system(check_warps)
+                    .with_system(handle_warps)
+                    .with_system(spawn_pending_map.after(handle_warps))
+                    .with_system(toggle_tile_style)
+                    .with_system(restyle_tiles.after(toggle_tile_style))
+                    .with_system(animate_tiles),
+            )
+            .add_system_set(SystemSet::on_resume(GameState::Overworld).with_system(show_map))
+            .add_system_set(SystemSet::on_pause(GameState::Overworld).with_system(hide_map));
+    }
+}
+
+pub struct LegendFile(Handle<RonAsset>);
+
+fn load_legend(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(LegendFile(assets.load(LEGEND_PATH)));
+}
+
+//A malformed legend keeps the last good one, reload_changed_maps rebuilds the map with it
+fn update_legend(
+    mut events: EventReader<AssetEvent<RonAsset>>,
+    file: Res<LegendFile>,
+    files: Res<Assets<RonAsset>>,
+    mut legend: ResMut<Legend>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if *handle != file.0 {
+            continue;
+        }
+        match files.get(handle).map(|file| Legend::from_ron(&file.0)) {
+            Some(Ok(loaded)) => *legend = loaded,
+            Some(Err(err)) => error!("Map legend {} is malformed: {}", LEGEND_PATH, err),
+            None => {}
+        }
+    }
+}
+
+fn create_simple_map(mut builder: MapBuilder) {
+    builder.request(START_MAP, Some(DEFAULT_SPAWN.to_string()));
+}
+
+fn layer_z(layer: MapLayer) -> f32 {
+    match layer {
+        MapLayer::Ground => 100.0,
+        MapLayer::Decoration => 110.0,
+        MapLayer::Collision => 0.0,
+        MapLayer::Objects => 120.0,
+    }
+}
+
+//Which atlas, index and colour a glyph is drawn with
+fn tile_look(
+    legend: &Legend,
+    sheets: &TileSheet,
+    ascii: &AsciiSheet,
+    glyph: char,
+    seconds: f32,
+) -> (Handle<TextureAtlas>, usize, Color) {
+    let entry = match legend.get(glyph) {
+        Some(entry) => entry,
+        //Stands out so missing legend entries get noticed
+        None => return (ascii.0.clone(), glyph as usize, Color::rgb(1.0, 0.0, 1.0)),
+    };
+    //The colour only tints ascii glyphs, tiles are drawn as painted
+    if let Some((sheet, index)) = &entry.tile {
+        if let Some(handle) = sheets.get(sheet) {
+            let index = entry
+                .animation
+                .as_ref()
+                .map_or(*index, |animation| animation.frame(seconds));
+            return (handle.clone(), index, Color::WHITE);
+        }
+    }
+    let color = Color::rgb(entry.color.0, entry.color.1, entry.color.2);
+    (ascii.0.clone(), legend.sprite(glyph), color)
+}
+
+//Everything needed to turn a map file into entities
+#[derive(SystemParam)]
+pub struct MapBuilder<'w, 's> {
+    pub commands: Commands<'w, 's>,
+    characters: Res<'w, CharacterSheet>,
+    legend: Res<'w, Legend>,
+    zones: Res<'w, EncounterZones>,
+    flags: Res<'w, WorldFlags>,
+    sheets: ResMut<'w, TileSheet>,
+    assets: Res<'w, AssetServer>,
+    atlases: ResMut<'w, Assets<TextureAtlas>>,
+    maps: Res<'w, Assets<MapAsset>>,
+    tiled_maps: Res<'w, Assets<TiledAsset>>,
+    ron_files: Res<'w, Assets<RonAsset>>,
+    chunks: ResMut<'w, MapChunks>,
+}
+
+//Layers, legend, objects and zone overrides of a map, ready to be spawned
+type LoadedMap = (MapData, Legend, MapObjects, Vec<((i32, i32), String)>);
+
+impl<'w, 's> MapBuilder<'w, 's> {
+    //Starts loading a map, spawn_pending_map builds it once it is ready
+    pub fn request(&mut self, name: &str, spawn: Option<String>) {
+        let source = match dungeon::parse_map_name(name) {
+            Some(_) => MapSource::Generated,
+            None => self.load_source(name, 0),
+        };
+        self.commands.insert_resource(PendingMap {
+            name: name.to_string(),
+            spawn,
+            source,
+            extension: 0,
+            objects: None,
+        });
+    }
+
+    fn load_source(&self, name: &str, extension: usize) -> MapSource {
+        let path = format!("maps/{}.{}", name, MAP_EXTENSIONS[extension]);
+        match MAP_EXTENSIONS[extension] {
+            "map" => MapSource::Text(self.assets.load(path.as_str())),
+            _ => MapSource::Tiled(self.assets.load(path.as_str())),
+        }
+    }
+
+    //Spawns the tiles and collision map of a map, returning its warp data,
+    //Ok(None) while the map file is still loading, Err when it is missing, broken or has no spawn
+    pub fn build(&mut self, pending: &mut PendingMap) -> Result<Option<CurrentMap>, String> {
+        //legend.ron and encounter_zones.ron load alongside the first map
+        if self.legend.0.is_empty() || self.zones.0.is_empty() {
+            return Ok(None);
+        }
+        let (mut data, legend, mut objects, zone_areas) = match self.load(pending)? {
+            Some(loaded) => loaded,
+            None => return Ok(None),
+        };
+        let objects_source = objects.clone();
+        let name = pending.name.as_str();
+        let source = data.clone();
+        apply_world_flags(&mut data, &legend, name, &objects, &self.flags);
+
+        let markers: Vec<_> = data.find_objects(&legend, ObjectType::Spawn).collect();
+        if let Some(marker) = markers.first() {
+            objects
+                .spawns
+                .entry(DEFAULT_SPAWN.to_string())
+                .or_insert(*marker);
+        }
+        if markers.len() > 1 {
+            warn!(
+                "Map {} has more than one spawn glyph, using the first",
+                name
+            );
+        }
+        if objects.spawns.is_empty() {
+            return Err(
+                "it has no spawn point, add a spawn glyph or a named spawn to it".to_string(),
+            );
+        }
+
+        let zones = &self.zones;
+        let mut collision_map = data.collision_map(&legend, |zone| zones.zone_by_name(zone));
+        for (position, zone) in zone_areas.iter() {
+            match zones.zone_by_name(zone) {
+                Some(zone_id) => {
+                    if let Some(mut flags) = collision_map.get(position.0, position.1) {
+                        flags.encounter_zone = Some(zone_id);
+                        collision_map.set(position.0, position.1, flags);
+                    }
+                }
+                None => warn!("Map {} uses unknown encounter zone {}", name, zone),
+            }
+        }
+
+        //Only objects get entities of their own, the tiles are meshed by chunk
+        let mut children = Vec::new();
+        let mut npcs = Vec::new();
+        for layer in MapLayer::VISIBLE {
+            for (position, glyph) in data.glyphs(layer) {
+                let entry = legend.get(glyph);
+                if entry.is_none() {
+                    warn!(
+                        "Map {} has unknown glyph {:?} at {:?}",
+                        name, glyph, position
+                    );
+                }
+                let object = entry.and_then(|entry| entry.object);
+                //NPCs walk around, so they are spawned as characters instead of tiles
+                if object == Some(ObjectType::Npc) {
+                    npcs.push(position);
+                    continue;
+                }
+
+                if let Some(interactable) =
+                    object.and_then(|object| objects.interactable_at(position, object))
+                {
+                    let transform =
+                        Transform::from_translation(tile_to_world(position, layer_z(layer)));
+                    children.push(
+                        self.commands
+                            .spawn_bundle((transform, GlobalTransform::from(transform)))
+                            .insert(interactable)
+                            .id(),
+                    );
+                }
+            }
+        }
+
+        for position in npcs {
+            let movement = objects
+                .npcs
+                .get(&position)
+                .cloned()
+                .unwrap_or(NpcMovement::Still);
+            children.push(spawn_npc(
+                &mut self.commands,
+                &self.characters,
+                position,
+                movement,
+            ));
+            collision_map.set_solid(position.0, position.1, true);
+        }
+
+        self.commands.insert_resource(collision_map);
+        let map = self
+            .commands
+            .spawn()
+            .insert(Map)
+            .insert(Name::new(format!("Map {}", name)))
+            .insert(Transform::default())
+            .insert(GlobalTransform::default())
+            .push_children(&children)
+            .id();
+
+        //The old chunk meshes go with the old map
+        self.chunks.map = Some(map);
+        self.chunks.meshes.clear();
+        self.chunks.animated.clear();
+        self.chunks.dirty = map_chunks(data.width(), data.height()).collect();
+
+        let handle = match &pending.source {
+            MapSource::Text(handle) => Some(handle.clone()),
+            _ => None,
+        };
+        Ok(Some(CurrentMap {
+            name: name.to_string(),
+            handle,
+            objects_handle: pending.objects.clone(),
+            data,
+            source,
+            legend,
+            warps: objects.warps,
+            spawns: objects.spawns,
+            roamers: objects.roamers,
+            dark: objects.dark,
+            objects_source,
+        }))
+    }
+
+    //Files that fail to load, because they are missing or malformed, make way for the next
+    //extension, the asset server has already logged why
+    fn load(&mut self, pending: &mut PendingMap) -> Result<Option<LoadedMap>, String> {
+        let name = pending.name.clone();
+        let state = match &pending.source {
+            MapSource::Generated => {
+                let (seed, floor) = dungeon::parse_map_name(&name)
+                    .ok_or_else(|| format!("{} isn't a dungeon floor", name))?;
+                return Ok(Some(generate_dungeon(seed, floor, &self.legend)));
+            }
+            MapSource::Text(handle) => {
+                if let Some(map) = self.maps.get(handle) {
+                    let assets = &self.assets;
+                    let objects = pending
+                        .objects
+                        .get_or_insert_with(|| assets.load(format!("maps/{}.ron", name).as_str()));
+                    let objects = match self.ron_files.get(&*objects) {
+                        Some(file) => file.parse().map_err(|err| {
+                            format!("its objects file maps/{}.ron is malformed: {}", name, err)
+                        })?,
+                        //Maps without signs, warps or the like have no objects file
+                        None if assets.get_load_state(&*objects) == LoadState::Failed => {
+                            MapObjects::default()
+                        }
+                        None => return Ok(None),
+                    };
+                    return Ok(Some((
+                        map.0.clone(),
+                        self.legend.clone(),
+                        objects,
+                        Vec::new(),
+                    )));
+                }
+                self.assets.get_load_state(handle)
+            }
+            MapSource::Tiled(handle) => {
+                if let Some(tiled) = self.tiled_maps.get(handle) {
+                    let tiled = tiled.0.clone();
+                    self.load_tilesets(&tiled);
+                    return Ok(Some(import_tiled(&tiled, &self.legend)));
+                }
+                self.assets.get_load_state(handle)
+            }
+        };
+        if state != LoadState::Failed {
+            return Ok(None);
+        }
+        pending.extension += 1;
+        if pending.extension == MAP_EXTENSIONS.len() {
+            return Err(format!("no map file for {} could be loaded", name));
+        }
+        pending.source = self.load_source(&name, pending.extension);
+        Ok(None)
+    }
+
+    fn load_tilesets(&mut self, tiled: &TiledMap) {
+        for tileset in tiled.tilesets.iter() {
+            let sheet = asset_path(&tileset.image);
+            if self.sheets.atlases.contains_key(&sheet) {
+                continue;
+            }
+            let image = self.assets.load(sheet.as_str());
+            let atlas = TextureAtlas::from_grid_with_padding(
+                image,
+                Vec2::new(tileset.tile_width as f32, tileset.tile_height as f32),
+                tileset.columns.max(1) as usize,
+                (tileset.tile_count / tileset.columns.max(1)) as usize,
+                Vec2::splat(tileset.spacing as f32),
+            );
+            let handle = self.atlases.add(atlas);
+            self.sheets.atlases.insert(sheet, handle);
+        }
+    }
+}
+
+//Object classes: spawn, warp (map, spawn), npc (patrol "x,y x,y" or wander radius),
+//zone (zone), sign (text), chest (item), switch (targets "x,y x,y") and roamer (enemy, level)
+fn import_tiled(
+    tiled: &TiledMap,
+    base_legend: &Legend,
+) -> (MapData, Legend, MapObjects, Vec<((i32, i32), String)>) {
+    let TiledTiles {
+        mut data,
+        mut legend,
+        skipped,
+    } = tiled.import_tiles();
+    for gid in skipped {
+        warn!("Tiled gid {} is too large to be imported", gid);
+    }
+    let mut objects = MapObjects::default();
+    let mut zone_areas = Vec::new();
+
+    for object in tiled.objects() {
+        let position = tiled.object_tile(object);
+        let property = |name: &str| object.properties.get(name).cloned();
+        match object.kind.to_lowercase().as_str() {
+            "spawn" => {
+                let name = match object.name.as_str() {
+                    "" => DEFAULT_SPAWN.to_string(),
+                    name => name.to_string(),
                 };
-                let tile = spawn_ascii_sprite(
-                    &mut commands,
-                    &ascii,
-                    char as usize,
-                    color,
-                    Vec3::new(x as f32 * TILE_SIZE, -(y as f32) * TILE_SIZE, 100.0),
-                    Vec3::splat(1.0),
-                );
-                if char == '#' {
-                    commands.entity(tile).insert(TileCollider);
+                objects.spawns.insert(name, position);
+            }
+            "warp" => match (property("map"), property("spawn")) {
+                (Some(map), Some(spawn)) => {
+                    for tile in tiled.object_tiles(object) {
+                        let warp = Warp {
+                            map: map.clone(),
+                            spawn: spawn.clone(),
+                        };
+                        objects.warps.insert(tile, warp);
+                    }
+                }
+                _ => warn!("Warp {} needs map and spawn properties", object.name),
+            },
+            "npc" => {
+                //Reuses the legend's NPC glyph so it looks like the others
+                let glyph = base_legend
+                    .0
+                    .iter()
+                    .find(|(_, entry)| entry.object == Some(ObjectType::Npc))
+                    .map(|(glyph, entry)| (*glyph, entry.clone()));
+                if let Some((glyph, entry)) = glyph {
+                    legend.0.insert(glyph, entry);
+                    data.set(MapLayer::Objects, position.0, position.1, glyph);
+                }
+                let movement = if let Some(patrol) = property("patrol") {
+                    NpcMovement::Patrol(parse_tiles(&patrol))
+                } else if let Some(radius) = property("wander") {
+                    NpcMovement::Wander(radius.parse().unwrap_or(1))
+                } else {
+                    NpcMovement::Still
+                };
+                objects.npcs.insert(position, movement);
+            }
+            "zone" => {
+                let zone = property("zone").unwrap_or_else(|| object.name.clone());
+                for tile in tiled.object_tiles(object) {
+                    zone_areas.push((tile, zone.clone()));
+                }
+            }
+            "sign" => {
+                objects
+                    .signs
+                    .insert(position, property("text").unwrap_or_default());
+            }
+            "chest" => {
+                objects
+                    .chests
+                    .insert(position, property("item").unwrap_or_default());
+            }
+            "switch" => {
+                let targets = parse_tiles(&property("targets").unwrap_or_default());
+                objects.switches.insert(position, targets);
+            }
+            "roamer" => {
+                let enemy = property("enemy").and_then(|enemy| ron::from_str(&enemy).ok());
+                match enemy {
+                    Some(enemy) => objects.roamers.push(RoamerSpawn {
+                        enemy,
+                        position,
+                        level: property("level")
+                            .and_then(|level| level.parse().ok())
+                            .unwrap_or(1),
+                    }),
+                    None => warn!("Roamer {} has no valid enemy", object.name),
+                }
+            }
+            other => warn!("Unknown Tiled object class {:?}", other),
+        }
+    }
+    (data, legend, objects, zone_areas)
+}
+
+//Generated floors are rebuilt from their seed every time, so they need no files
+fn generate_dungeon(seed: u64, floor: u32, legend: &Legend) -> LoadedMap {
+    let generated = dungeon::generate(seed, floor, &DungeonSettings::default());
+    let up = match floor {
+        1 => Warp {
+            map: DUNGEON_ENTRANCE.0.to_string(),
+            spawn: DUNGEON_ENTRANCE.1.to_string(),
+        },
+        _ => Warp {
+            map: dungeon::map_name(seed, floor - 1),
+            spawn: dungeon::STAIRS_SPAWN.to_string(),
+        },
+    };
+    let down = Warp {
+        map: dungeon::map_name(seed, floor + 1),
+        spawn: DEFAULT_SPAWN.to_string(),
+    };
+
+    let objects = MapObjects {
+        dark: true,
+        chests: generated.chests.iter().cloned().collect(),
+        warps: HashMap::from([(generated.stairs_up, up), (generated.stairs_down, down)]),
+        spawns: generated
+            .spawns()
+            .iter()
+            .map(|(name, tile)| (name.to_string(), *tile))
+            .collect(),
+        //Bosses get tougher the deeper the floor
+        roamers: vec![RoamerSpawn {
+            enemy: EnemyType::Ghost,
+            position: generated.boss,
+            level: 2 + 2 * floor as usize,
+        }],
+        ..Default::default()
+    };
+    (generated.data, legend.clone(), objects, Vec::new())
+}
+
+//Writes an edited map back to its .map and .ron files, the file watcher then rebuilds it
+pub fn save_map(current_map: &CurrentMap) -> Result<(), String> {
+    if current_map.handle.is_none() {
+        return Err(format!(
+            "{} isn't a .map file, only those can be saved",
+            current_map.name
+        ));
+    }
+    let name = current_map.name.as_str();
+    let (data, legend) = (&current_map.source, &current_map.legend);
+    let mut objects = current_map.objects_source.clone();
+
+    //Entries of objects that were painted over go with them
+    let placed = |object| data.find_objects(legend, object).collect::<HashSet<_>>();
+    let signs = placed(ObjectType::Sign);
+    let chests = placed(ObjectType::Chest);
+    let switches = placed(ObjectType::Switch);
+    let npcs = placed(ObjectType::Npc);
+    objects.signs.retain(|tile, _| signs.contains(tile));
+    objects.chests.retain(|tile, _| chests.contains(tile));
+    objects.switches.retain(|tile, _| switches.contains(tile));
+    objects.npcs.retain(|tile, _| npcs.contains(tile));
+
+    //The default spawn comes from the spawn glyph when there is one
+    let marker = data.find_objects(legend, ObjectType::Spawn).next();
+    objects.spawns = current_map
+        .spawns
+        .iter()
+        .filter(|(spawn, tile)| spawn.as_str() != DEFAULT_SPAWN || marker != Some(**tile))
+        .map(|(spawn, tile)| (spawn.clone(), *tile))
+        .collect();
+
+    let objects = ron::ser::to_string_pretty(&objects, ron::ser::PrettyConfig::new())
+        .map_err(|err| err.to_string())?;
+    for (extension, contents) in [("map", data.to_text()), ("ron", objects)] {
+        let path = format!("assets/maps/{}.{}", name, extension);
+        fs::write(&path, contents).map_err(|err| format!("{}: {}", path, err))?;
+    }
+    Ok(())
+}
+
+//Opened chests and flipped switches are baked into the map before anything spawns
+fn apply_world_flags(
+    data: &mut MapData,
+    legend: &Legend,
+    name: &str,
+    objects: &MapObjects,
+    flags: &WorldFlags,
+) {
+    for (x, y) in objects.chests.keys() {
+        if flags.opened_chests.contains(&(name.to_string(), *x, *y)) {
+            data.toggle(legend, *x, *y);
+        }
+    }
+    for ((x, y), targets) in objects.switches.iter() {
+        if flags.switches_on.contains(&(name.to_string(), *x, *y)) {
+            data.toggle(legend, *x, *y);
+            for target in targets {
+                data.toggle(legend, target.0, target.1);
+            }
+        }
+    }
+}
+
+//Brings the tile meshes and collision map in line after the map data was edited
+pub fn sync_tile(
+    chunks: &mut MapChunks,
+    collision_map: &mut CollisionMap,
+    current_map: &CurrentMap,
+    position: (i32, i32),
+) {
+    chunks.mark_dirty(position);
+    collision_map.set_solid(
+        position.0,
+        position.1,
+        current_map
+            .data
+            .is_solid(&current_map.legend, position.0, position.1),
+    );
+}
+
+fn toggle_tile_style(actions: Res<InputActions>, mut sheets: ResMut<TileSheet>) {
+    if actions.just_pressed(InputAction::ToggleTiles) {
+        sheets.toggle_style();
+    }
+}
+
+//Redraws every tile of the map after switching between ascii and graphics
+fn restyle_tiles(mut chunks: ResMut<MapChunks>, sheets: Res<TileSheet>) {
+    if sheets.is_changed() {
+        chunks.mark_all_dirty();
+    }
+}
+
+//Animated tiles share one clock, a frame change only rewrites the texture coordinates of their quads
+fn animate_tiles(
+    chunks: Res<MapChunks>,
+    current_map: Res<CurrentMap>,
+    sheets: Res<TileSheet>,
+    clock: Res<TileClock>,
+    batch_query: Query<&Mesh2dHandle>,
+    atlases: Res<Assets<TextureAtlas>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut shown: Local<HashMap<char, usize>>,
+) {
+    if sheets.style == TileStyle::Ascii {
+        return;
+    }
+    for (glyph, placed) in chunks.animated.iter() {
+        let (animation, sheet) = match current_map.legend.get(*glyph) {
+            Some(LegendEntry {
+                animation: Some(animation),
+                tile: Some((sheet, _)),
+                ..
+            }) => (animation, sheet),
+            _ => continue,
+        };
+        let frame = animation.frame(clock.0);
+        if shown.insert(*glyph, frame) == Some(frame) {
+            continue;
+        }
+        let atlas = match sheets.get(sheet).and_then(|atlas| atlases.get(atlas)) {
+            Some(atlas) => atlas,
+            None => continue,
+        };
+        for placed in placed.iter() {
+            let mesh = batch_query
+                .get(placed.batch)
+                .ok()
+                .and_then(|handle| meshes.get_mut(&handle.0));
+            if let Some(mesh) = mesh {
+                for quad in placed.quads.iter() {
+                    set_quad_index(mesh, atlas, *quad, frame);
+                }
+            }
+        }
+    }
+}
+
+//Rebuilds the meshes of every chunk that changed, one per layer, texture and tint
+fn mesh_map_chunks(
+    mut batcher: MeshBatcher,
+    mut chunks: ResMut<MapChunks>,
+    current_map: Res<CurrentMap>,
+    sheets: Res<TileSheet>,
+    ascii: Res<AsciiSheet>,
+    clock: Res<TileClock>,
+) {
+    let map = match chunks.map {
+        Some(map) if !chunks.dirty.is_empty() => map,
+        _ => return,
+    };
+    let (data, legend) = (&current_map.data, &current_map.legend);
+    let dirty = std::mem::take(&mut chunks.dirty);
+    for chunk in dirty {
+        for placed in chunks.animated.values_mut() {
+            placed.retain(|placed| placed.chunk != chunk);
+        }
+        let mut groups: HashMap<_, ChunkBatch> = HashMap::new();
+        for layer in MapLayer::VISIBLE {
+            for position in chunk_tiles(chunk, data.width(), data.height()) {
+                let glyph = data.get(layer, position.0, position.1);
+                if glyph == EMPTY {
+                    continue;
                 }
-                if char == '@' {
-                    commands
-                        .entity(tile)
-                        .insert(Npc::Healer)
-                        .insert(TileCollider);
+                let entry = legend.get(glyph);
+                let object = entry.and_then(|entry| entry.object);
+                if matches!(object, Some(ObjectType::Npc | ObjectType::Spawn)) {
+                    continue;
                 }
-                if char == '~' {
-                    commands.entity(tile).insert(EncounterSpawner);
+                let (atlas, index, color) = tile_look(legend, &sheets, &ascii, glyph, clock.0);
+                let animated = entry.is_some_and(|entry| entry.animation.is_some());
+                let (_, _, quads, frames) = groups
+                    .entry((layer, atlas.id, color.as_rgba_u32()))
+                    .or_insert_with(|| (atlas.clone(), color, Vec::new(), Vec::new()));
+                //Ascii glyphs don't animate
+                if animated && atlas != ascii.0 {
+                    frames.push((glyph, quads.len()));
                 }
-                tiles.push(tile);
+                quads.push(Quad {
+                    center: tile_to_world(position, 0.0),
+                    size: Vec2::splat(TILE_SIZE),
+                    index,
+                });
             }
         }
+
+        //A plain despawn would leave the old meshes in the map's Children
+        for old in chunks.meshes.remove(&chunk).unwrap_or_default() {
+            batcher.commands.entity(old).despawn_recursive();
+        }
+        let mut meshes = Vec::new();
+        for ((layer, _, _), (atlas, color, quads, frames)) in groups {
+            let transform = Transform::from_xyz(0.0, 0.0, layer_z(layer));
+            let batch = batcher.spawn_batch(&atlas, color, &quads, transform);
+            meshes.push(batch);
+            let mut by_glyph: HashMap<char, Vec<usize>> = HashMap::new();
+            for (glyph, quad) in frames {
+                by_glyph.entry(glyph).or_default().push(quad);
+            }
+            for (glyph, quads) in by_glyph {
+                chunks
+                    .animated
+                    .entry(glyph)
+                    .or_default()
+                    .push(AnimatedQuads {
+                        chunk,
+                        batch,
+                        quads,
+                    });
+            }
+        }
+        batcher.commands.entity(map).push_children(&meshes);
+        chunks.meshes.insert(chunk, meshes);
     }
+}
 
-    commands
-        .spawn()
-        .insert(Map)
-        .insert(Name::new("Map"))
-        .insert(Transform::default())
-        .insert(GlobalTransform::default())
-        .push_children(&tiles);
+//Starts a warp when the player steps onto a warp tile
+fn check_warps(
+    mut commands: Commands,
+    mut player_query: Query<(&mut Player, &Transform)>,
+    current_map: Res<CurrentMap>,
+    ascii: Res<AsciiSheet>,
+    mut last_tile: Local<Option<(i32, i32)>>,
+) {
+    let (mut player, transform) = player_query.single_mut();
+    let tile = world_to_tile(transform.translation);
+    //Arriving on a warp tile should not send the player straight back
+    if *last_tile == Some(tile) || current_map.is_changed() {
+        *last_tile = Some(tile);
+        return;
+    }
+    *last_tile = Some(tile);
+
+    if !player.active {
+        return;
+    }
+    if let Some(warp) = current_map.warps.get(&tile) {
+        player.active = false;
+        create_warp_fadeout(&mut commands, warp.clone(), &ascii);
+    }
+}
+
+fn handle_warps(mut builder: MapBuilder, mut warp_events: EventReader<WarpEvent>) {
+    for WarpEvent(warp) in warp_events.iter() {
+        builder.request(&warp.map, Some(warp.spawn.clone()));
+    }
+}
+
+//Swaps the old map for the pending one once its file is ready
+fn spawn_pending_map(
+    mut builder: MapBuilder,
+    pending: Option<ResMut<PendingMap>>,
+    map_query: Query<Entity, With<Map>>,
+    mut player_query: Query<(&mut Player, &mut Transform)>,
+    mut camera: ResMut<CameraController>,
+) {
+    let mut pending = match pending {
+        Some(pending) => pending,
+        None => return,
+    };
+    let current_map = match builder.build(&mut pending) {
+        Ok(Some(current_map)) => current_map,
+        Ok(None) => return,
+        //The player stays on the current map, let go of the warp that led here
+        Err(err) => {
+            warn!("Could not load map {}, {}", pending.name, err);
+            if pending.spawn.is_some() {
+                player_query.single_mut().0.active = true;
+            }
+            builder.commands.remove_resource::<PendingMap>();
+            return;
+        }
+    };
+    for map in map_query.iter() {
+        builder.commands.entity(map).despawn_recursive();
+    }
+
+    //Reloads keep the player where they are
+    if let Some(spawn) = &pending.spawn {
+        let (mut player, mut transform) = player_query.single_mut();
+        let tile = current_map.spawns.get(spawn).or_else(|| {
+            warn!(
+                "Map {} has no spawn named {}, using its default spawn",
+                pending.name, spawn
+            );
+            current_map.spawns.get(DEFAULT_SPAWN)
+        });
+        if let Some(tile) = tile {
+            transform.translation = tile_to_world(*tile, transform.translation.z);
+        }
+        player.active = true;
+        camera.position = transform.translation.truncate();
+    }
+    builder.commands.remove_resource::<PendingMap>();
+    builder.commands.insert_resource(current_map);
+}
+
+//Rebuilds the map when its files, the legend or the encounter zones are saved during development
+fn reload_changed_maps(
+    mut builder: MapBuilder,
+    mut map_events: EventReader<AssetEvent<MapAsset>>,
+    mut ron_events: EventReader<AssetEvent<RonAsset>>,
+    legend_file: Res<LegendFile>,
+    zones_file: Res<ZonesFile>,
+    current_map: Res<CurrentMap>,
+) {
+    let mut changed = false;
+    for event in map_events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            changed |= current_map.handle.as_ref() == Some(handle);
+        }
+    }
+    for event in ron_events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            changed |= *handle == legend_file.0
+                || *handle == zones_file.0
+                || current_map.objects_handle.as_ref() == Some(handle);
+        }
+    }
+    //Nothing to rebuild before the first map
+    if changed && !current_map.name.is_empty() {
+        info!("Map {} changed on disk, rebuilding it", current_map.name);
+        builder.request(&current_map.name, None);
+    }
 }
 
 fn hide_map(
@@ -104,3 +1042,64 @@ fn show_map(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn generated_floors_link_up_and_down() {
+        let (data, _, objects, _) = generate_dungeon(7, 2, &Legend::default());
+        for spawn in [DEFAULT_SPAWN, dungeon::STAIRS_SPAWN] {
+            let tile = objects.spawns[spawn];
+            assert_ne!(data.get(MapLayer::Ground, tile.0, tile.1), '#', "{}", spawn);
+        }
+        let targets: HashSet<_> = objects
+            .warps
+            .values()
+            .map(|warp| (warp.map.clone(), warp.spawn.clone()))
+            .collect();
+        assert!(targets.contains(&(dungeon::map_name(7, 1), dungeon::STAIRS_SPAWN.to_string())));
+        assert!(targets.contains(&(dungeon::map_name(7, 3), DEFAULT_SPAWN.to_string())));
+    }
+
+    #[test]
+    fn imports_tiled_properties() {
+        let tmj = r#"{
+            "width": 3, "height": 1, "tilewidth": 16, "tileheight": 16,
+            "tilesets": [{"firstgid": 1, "name": "terrain", "image": "terrain.png",
+                "tilewidth": 16, "tileheight": 16, "columns": 3, "tilecount": 3,
+                "tiles": [
+                    {"id": 1, "properties": [{"name": "collider", "type": "bool", "value": true}]},
+                    {"id": 2, "properties": [{"name": "zone", "type": "string", "value": "forest"}]}
+                ]}],
+            "layers": [
+                {"type": "tilelayer", "name": "ground", "data": [1, 2, 3]},
+                {"type": "objectgroup", "name": "markers", "objects": [
+                    {"name": "", "type": "spawn", "x": 0, "y": 0},
+                    {"name": "meadow", "type": "zone", "x": 32, "y": 0, "width": 16, "height": 16}
+                ]}
+            ]
+        }"#;
+        let tiled = TiledMap::parse(tmj, Path::new("maps/test.tmj"), &Default::default()).unwrap();
+        let (data, legend, objects, zone_areas) = import_tiled(&tiled, &Legend::default());
+
+        assert!(!data.is_solid(&legend, 0, 0));
+        assert!(data.is_solid(&legend, 1, 0));
+        assert_eq!(data.zone(&legend, 2, 0), Some("forest"));
+        assert_eq!(data.zone(&legend, 0, 0), None);
+        assert_eq!(objects.spawns[DEFAULT_SPAWN], (0, 0));
+        assert_eq!(zone_areas, vec![((2, 0), "meadow".to_string())]);
+    }
+
+    #[test]
+    fn first_floor_leads_back_to_the_entrance() {
+        let (_, _, objects, _) = generate_dungeon(7, 1, &Legend::default());
+        assert!(objects
+            .warps
+            .values()
+            .any(|warp| (warp.map.as_str(), warp.spawn.as_str()) == DUNGEON_ENTRANCE));
+    }
+}